@@ -1,12 +1,7 @@
-use anyhow::{anyhow, Result};
-use lazy_static::lazy_static;
-use regex::Regex;
+use anyhow::Result;
+use parsing::{one_char_of, parse_all, unsigned, ParseError, Parser};
 use std::io::BufRead;
 
-lazy_static! {
-    static ref RE_INSTRUCTION: Regex = Regex::new(r"(N|S|E|W|F|L|R)(\d+)").unwrap();
-}
-
 #[derive(Copy, Clone, Debug)]
 struct Point {
     x: i32,
@@ -30,30 +25,29 @@ enum Instruction {
     Rotate(Rotation),
 }
 
-fn rotation_from_degrees(deg: i32) -> Result<Rotation> {
+fn rotation_from_degrees(deg: i32) -> Result<Rotation, String> {
     match deg {
         90 => Ok(Rotation::Rotate90),
         180 => Ok(Rotation::Rotate180),
         270 => Ok(Rotation::Rotate270),
-        _ => Err(anyhow!("Invalid rotation degree: {}", deg)),
+        _ => Err("a rotation of 90, 180 or 270 degrees".to_owned()),
     }
 }
 
 impl Instruction {
-    fn parse(s: &str) -> Result<Instruction> {
-        let err = || anyhow!("Invalid instuction: {}", s);
-        let caps = RE_INSTRUCTION.captures(s).ok_or_else(err)?;
-        let nb: i32 = caps[2].parse()?;
-        match &(caps[1]) {
-            "N" => Ok(Instruction::N(nb)),
-            "S" => Ok(Instruction::S(nb)),
-            "E" => Ok(Instruction::E(nb)),
-            "W" => Ok(Instruction::W(nb)),
-            "F" => Ok(Instruction::F(nb)),
-            "L" => Ok(Instruction::Rotate(rotation_from_degrees(nb)?)),
-            "R" => Ok(Instruction::Rotate(rotation_from_degrees(360 - nb)?)),
-            _ => Err(err()),
-        }
+    fn parse(s: &str) -> Result<Instruction, ParseError> {
+        let instruction = one_char_of("NSEWFLR")
+            .then(unsigned::<i32>())
+            .try_map(|(action, nb)| match action {
+                'N' => Ok(Instruction::N(nb)),
+                'S' => Ok(Instruction::S(nb)),
+                'E' => Ok(Instruction::E(nb)),
+                'W' => Ok(Instruction::W(nb)),
+                'F' => Ok(Instruction::F(nb)),
+                'L' => Ok(Instruction::Rotate(rotation_from_degrees(nb)?)),
+                _ => Ok(Instruction::Rotate(rotation_from_degrees(360 - nb)?)),
+            });
+        parse_all(&instruction, s)
     }
 }
 
@@ -115,7 +109,8 @@ fn main() -> Result<()> {
     let instructions: Result<Vec<Instruction>> = std::io::stdin()
         .lock()
         .lines()
-        .map(|line| Instruction::parse(&line?))
+        .enumerate()
+        .map(|(i, line)| Ok(Instruction::parse(&line?).map_err(|e| e.at_line(i + 1))?))
         .collect();
     let instructions = instructions?;
 
@@ -1,43 +1,36 @@
-use anyhow::{anyhow, Result};
-use lazy_static::lazy_static;
-use regex::{Captures, Regex};
+use anyhow::Result;
+use parsing::{parse_all, tag, take_while1, unsigned, ParseError, Parser};
 use std::{collections::HashMap, fmt, io::BufRead};
 
-lazy_static! {
-    static ref MASK_RE: Regex = Regex::new(r"mask = ((0|1|X){36})").unwrap();
-    static ref MEM_RE: Regex = Regex::new(r"mem\[(\d+)\] = (\d+)").unwrap();
-}
-
 enum Op {
     Mask { or: u64, and: u64, float: u64 },
     Set { addr: u64, value: u64 },
 }
 
 impl Op {
-    fn parse(s: &str) -> Result<Op> {
-        let mask_caps = MASK_RE.captures(s);
-        if let Some(caps) = mask_caps {
-            return Op::parse_mask(&caps);
-        }
-
-        let mem_caps = MEM_RE.captures(s);
-        if let Some(caps) = mem_caps {
-            return Op::parse_mem(&caps);
-        }
-
-        Err(anyhow!("Invalid op: {}", s))
+    fn parse(s: &str) -> Result<Op, ParseError> {
+        let mask_bits = take_while1("mask bits", |c| "01X".contains(c)).try_map(Op::parse_mask);
+        let mask = tag("mask = ").ignore_then(mask_bits);
+        let mem = tag("mem[")
+            .ignore_then(unsigned::<u64>())
+            .then_ignore(tag("] = "))
+            .then(unsigned::<u64>())
+            .map(|(addr, value)| Op::Set { addr, value });
+        parse_all(&mask.or(mem), s)
     }
 
-    fn parse_mask(caps: &Captures) -> Result<Op> {
+    fn parse_mask(bits: &str) -> Result<Op, String> {
+        if bits.len() != 36 {
+            return Err("exactly 36 mask bits".to_owned());
+        }
         let mut or_mask: u64 = 0;
         let mut and_mask: u64 = u64::MAX;
         let mut float_mask: u64 = 0;
-        for (i, bit) in caps[1].chars().enumerate() {
+        for (i, bit) in bits.chars().enumerate() {
             match bit {
                 '0' => and_mask &= !(1u64 << (35 - i)),
                 '1' => or_mask |= 1u64 << (35 - i),
-                'X' => float_mask |= 1u64 << (35 - i),
-                _ => return Err(anyhow!("Found invalid mask op: {}", &caps[0])),
+                _ => float_mask |= 1u64 << (35 - i),
             }
         }
         Ok(Op::Mask {
@@ -46,13 +39,6 @@ impl Op {
             float: float_mask,
         })
     }
-
-    fn parse_mem(caps: &Captures) -> Result<Op> {
-        Ok(Op::Set {
-            addr: caps[1].parse::<u64>()?,
-            value: caps[2].parse::<u64>()?,
-        })
-    }
 }
 
 fn part1(ops: &[Op]) {
@@ -176,7 +162,7 @@ fn part2(ops: &[Op]) {
 
     let sum: i64 = mem
         .iter()
-        .map(|m| m.value * 2i64.pow(m.address.floating.count_ones() as u32))
+        .map(|m| m.value * 2i64.pow(m.address.floating.count_ones()))
         .sum();
     println!("Part2 mem sum: {}", sum);
 }
@@ -185,7 +171,8 @@ fn main() -> Result<()> {
     let ops: Result<Vec<Op>> = std::io::stdin()
         .lock()
         .lines()
-        .map(|line| Op::parse(line?.as_str()))
+        .enumerate()
+        .map(|(i, line)| Ok(Op::parse(line?.as_str()).map_err(|e| e.at_line(i + 1))?))
         .collect();
     let ops = ops?;
 
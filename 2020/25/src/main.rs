@@ -0,0 +1,31 @@
+use anyhow::{anyhow, Result};
+use numtheory::{discrete_log, mod_pow};
+use std::io::BufRead;
+
+const SUBJECT: i64 = 7;
+const MODULUS: i64 = 20201227;
+
+// The handshake transforms the subject number `loop_size` times, which is just
+// SUBJECT^loop_size mod MODULUS. Recovering the loop size from a public key is
+// a discrete logarithm.
+fn loop_size(public_key: i64) -> Result<i64> {
+    discrete_log(SUBJECT, public_key, MODULUS)
+        .ok_or_else(|| anyhow!("No loop size produces public key {}", public_key))
+}
+
+fn main() -> Result<()> {
+    let keys: Result<Vec<i64>> = std::io::stdin()
+        .lock()
+        .lines()
+        .map(|line| Ok(line?.trim().parse::<i64>()?))
+        .collect();
+    let keys = keys?;
+    if keys.len() != 2 {
+        return Err(anyhow!("Expecting two public keys, got {}", keys.len()));
+    }
+    let (card_key, door_key) = (keys[0], keys[1]);
+
+    let card_loop_size = loop_size(card_key)?;
+    println!("Encryption key: {}", mod_pow(door_key, card_loop_size, MODULUS));
+    Ok(())
+}
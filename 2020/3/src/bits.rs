@@ -0,0 +1,58 @@
+// Rows of the map are stored a bit per column, set where the square holds a
+// given terrain: column x lives in bit x % 64 of word x / 64 of its row.
+
+pub fn words_for(width: usize) -> usize {
+    width.div_ceil(64)
+}
+
+// Appends the packed form of an ASCII `line` to `words`, setting the bits of
+// the characters accepted by `is_set`.
+pub fn pack(line: &str, words: &mut Vec<u64>, is_set: impl Fn(char) -> bool) {
+    let start = words.len();
+    words.resize(start + words_for(line.len()), 0);
+    for (x, c) in line.chars().enumerate() {
+        if is_set(c) {
+            words[start + x / 64] |= 1 << (x % 64);
+        }
+    }
+}
+
+// Sets and clears bit `x` of a packed row, e.g. to build a mask of columns.
+pub fn set(words: &mut [u64], x: usize) {
+    words[x / 64] |= 1 << (x % 64);
+}
+
+pub fn clear(words: &mut [u64], x: usize) {
+    words[x / 64] &= !(1 << (x % 64));
+}
+
+// A borrowed packed row.
+#[derive(Copy, Clone)]
+pub struct Row<'a> {
+    words: &'a [u64],
+    width: usize,
+}
+
+impl<'a> Row<'a> {
+    pub fn new(words: &'a [u64], width: usize) -> Row<'a> {
+        debug_assert_eq!(words.len(), words_for(width));
+        Row { words, width }
+    }
+
+    pub fn get(&self, x: usize) -> bool {
+        self.words[x / 64] >> (x % 64) & 1 == 1
+    }
+
+    // The columns set both in this row and in `mask`, which is packed the
+    // same way, written to `out`. Returns whether there are any.
+    pub fn and(&self, mask: &[u64], out: &mut Vec<u64>) -> bool {
+        out.clear();
+        out.extend(self.words.iter().zip(mask).map(|(word, m)| word & m));
+        out.iter().any(|&word| word != 0)
+    }
+
+    // The column within the pattern that `x` lands on.
+    pub fn wrap(&self, x: i64) -> usize {
+        x.rem_euclid(self.width as i64) as usize
+    }
+}
@@ -1,15 +1,129 @@
-use std::io::BufRead;
 use anyhow::anyhow;
+use bits::Row;
+use numtheory::gcd;
+use route::Move;
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+use sweep::{SearchRange, SearchResult, SlopeCount, SlopeCounter};
+use terrain::{Costs, Legend, Tally, Terrain, TerrainRow};
 
+mod bits;
+mod render;
+mod route;
+mod sweep;
+mod terrain;
+
+// Moves `right` columns every `down` rows. `right` is a fraction so slopes
+// like "1 right per 3 down" can visit every row; the column is then rounded
+// down. Negative fractions move left.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Slope {
+    right: i64,
+    right_den: i64,
+    down: usize,
+}
+
+impl Slope {
+    fn new(right: i64, down: usize) -> Slope {
+        Slope::fractional(right, 1, down)
+    }
+
+    fn fractional(right: i64, right_den: i64, down: usize) -> Slope {
+        assert!(right_den > 0 && down > 0, "Slopes need to move down");
+        let d = gcd(right, right_den);
+        Slope {
+            right: right / d,
+            right_den: right_den / d,
+            down,
+        }
+    }
+
+    // The width of a map `width` columns wide in units of 1 / right_den, which
+    // is how far the column goes before it wraps. Fine enough fractions don't
+    // fit an i64 on wide maps.
+    fn span(&self, width: usize) -> anyhow::Result<i64> {
+        (width as i64).checked_mul(self.right_den).ok_or_else(|| {
+            anyhow!("Slope {} is too fine for a map {} columns wide", self, width)
+        })
+    }
+
+    // Column and row after `step` steps from the top left corner, given the
+    // `span` of the map. Whole copies of the pattern are taken out of `right`
+    // first, which lands on the same square but keeps the column from
+    // overflowing.
+    fn position(&self, step: u64, span: i64) -> (i64, usize) {
+        let right = self.right % span;
+        let x = (step as i128 * right as i128).div_euclid(self.right_den as i128) as i64;
+        let y = step as usize * self.down;
+        (x, y)
+    }
+}
+
+impl fmt::Display for Slope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.right_den == 1 {
+            write!(f, "{},{}", self.right, self.down)
+        } else {
+            write!(f, "{}/{},{}", self.right, self.right_den, self.down)
+        }
+    }
+}
+
+// Parses "right,down" where right is an integer or a fraction, e.g. "3,1",
+// "-1,2" or "1/3,1".
+impl FromStr for Slope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Slope> {
+        let err = || anyhow!("Invalid slope, expecting right,down: {}", s);
+        let mut parts = s.split(',');
+        let right = parts.next().ok_or_else(err)?;
+        let down: usize = parts.next().ok_or_else(err)?.parse()?;
+        if parts.next().is_some() {
+            return Err(err());
+        }
+        let (num, den) = match right.split_once('/') {
+            Some((num, den)) => (num.parse::<i64>()?, den.parse::<i64>()?),
+            None => (right.parse::<i64>()?, 1),
+        };
+        if den <= 0 || down == 0 {
+            return Err(anyhow!("Slope {} needs a positive denominator and down step", s));
+        }
+        Ok(Slope::fractional(num, den, down))
+    }
+}
+
+// `column + step` taken back within `0..span`, where both already are. The
+// sum itself could overflow when `span` is close to the limit of an i64.
+fn wrap_add(column: i64, step: i64, span: i64) -> i64 {
+    if column < span - step {
+        column + step
+    } else {
+        column - (span - step)
+    }
+}
+
+// What a slope hits when the map also wraps vertically: the number of steps
+// until the trajectory is back at its start, and the trees hit on the way.
+struct Cycle {
+    steps: u64,
+    trees: u64,
+}
+
+// Rows are bit-packed, see `bits`, with a plane per obstacle, so wide and
+// tall maps stay small and looking up a square is a few shifts and masks.
 struct Map {
-    trees: Vec<bool>,
+    planes: [Vec<u64>; 3],
+    legend: Legend,
+    words_per_row: usize,
     width: usize,
     height: usize,
 }
 
 impl Map {
-    pub fn parse(iter: impl Iterator<Item=std::io::Result<String>>) -> anyhow::Result<Map> {
-        let mut result = Map { trees: Vec::new(), width: 0, height: 0 };
+    pub fn parse(iter: impl Iterator<Item=std::io::Result<String>>, legend: Legend) -> anyhow::Result<Map> {
+        let mut result = Map { planes: Default::default(), legend, words_per_row: 0, width: 0, height: 0 };
         for line in iter {
             result.add_line(&line?)?;
         }
@@ -17,59 +131,349 @@ impl Map {
     }
 
     pub fn add_line(&mut self, line: &str) -> anyhow::Result<()> {
+        self.legend.check(line).map_err(|e| anyhow!("Line {}: {}", self.height + 1, e))?;
+        if line.is_empty() {
+            return Err(anyhow!("Line {}: the map can't have empty lines", self.height + 1));
+        }
         if self.width == 0 {
             self.width = line.len();
+            self.words_per_row = bits::words_for(self.width);
         } else if self.width != line.len() {
             return Err(anyhow!("Expecting all input lines to have the same length"));
         }
 
         self.height += 1;
-        self.trees.extend(line.chars().map(|x| x == '#'));
+        self.legend.pack(line, &mut self.planes);
 
         Ok(())
     }
 
-    pub fn is_tree(&self, x: usize, y: usize) -> bool {
+    pub fn row(&self, y: usize) -> TerrainRow<'_> {
+        let start = y * self.words_per_row;
+        let plane = |i: usize| {
+            Row::new(&self.planes[i][start..start + self.words_per_row], self.width)
+        };
+        TerrainRow::new([plane(0), plane(1), plane(2)])
+    }
+
+    pub fn terrain(&self, x: i64, y: usize) -> Terrain {
         if y >= self.height {
-            return false;
+            return Terrain::Open;
         }
-        let real_x = x % self.width;
-        let index = y * self.width + real_x;
-        self.trees[index]
+        self.row(y).get_wrapped(x)
     }
 
     pub fn is_end(&self, y: usize) -> bool {
         y >= self.height
     }
 
-    pub fn count_slope(&self, dx: usize, dy: usize) -> usize {
-        let mut x = 0; 
-        let mut y = 0; 
-        let mut count = 0;
-        while !self.is_end(y) {
-            if self.is_tree(x, y) {
-                count += 1;
-            } 
-            x += dx;
-            y += dy;
+    // The squares `slope` visits on its way down.
+    pub fn trajectory(&self, slope: Slope) -> anyhow::Result<Vec<(i64, usize)>> {
+        let span = slope.span(self.width)?;
+        Ok((0..)
+            .map(|step| slope.position(step, span))
+            .take_while(|(_, y)| !self.is_end(*y))
+            .collect())
+    }
+
+    // Counts all `slopes` in a single pass over the map.
+    pub fn count_slopes(&self, slopes: &[Slope]) -> anyhow::Result<Vec<SlopeCount>> {
+        let mut counter = SlopeCounter::new(slopes, self.width)?;
+        for y in 0..self.height {
+            counter.add_row(self.row(y));
+        }
+        Ok(counter.finish().0)
+    }
+
+    // Same as count_slopes for a single slope, without any division in the
+    // loop when `right` is an integer. The column is tracked in units of
+    // 1 / right_den and kept within one copy of the pattern, so wrapping is a
+    // subtraction.
+    pub fn count_slope(&self, slope: Slope) -> anyhow::Result<Tally> {
+        let mut tally = Tally::default();
+        if self.width == 0 {
+            return Ok(tally);
         }
-        count
+        let span = slope.span(self.width)?;
+        let step = slope.right.rem_euclid(span);
+        let mut column = 0;
+        for y in (0..self.height).step_by(slope.down) {
+            tally.add(self.row(y).get((column / slope.right_den) as usize));
+            column = wrap_add(column, step, span);
+        }
+        Ok(tally)
+    }
+
+    // Number of steps until `slope` gets back to the top left corner when the
+    // map wraps in both directions. The row comes back every
+    // height / gcd(down, height) steps. The column also has to come back with
+    // no fractional part left over, which takes
+    // width * right_den / gcd(right, width * right_den) steps. Both periods
+    // fit an i64 but their lcm may not.
+    pub fn cycle_length(&self, slope: Slope) -> anyhow::Result<u64> {
+        let height = self.height as i64;
+        let span = slope.span(self.width)?;
+        let row_period = height / gcd(slope.down as i64, height);
+        let column_period = span / gcd(slope.right, span);
+        (row_period / gcd(row_period, column_period))
+            .checked_mul(column_period)
+            .map(|steps| steps as u64)
+            .ok_or_else(|| anyhow!("Slope {} takes too many steps to cycle", slope))
+    }
+
+    pub fn count_toroidal(&self, slope: Slope) -> anyhow::Result<Cycle> {
+        let steps = self.cycle_length(slope)?;
+        let span = slope.span(self.width)?;
+        let trees = (0..steps)
+            .map(|step| slope.position(step, span))
+            .filter(|(x, y)| self.terrain(*x, y % self.height) == Terrain::Tree)
+            .count() as u64;
+        Ok(Cycle { steps, trees })
     }
 }
 
-fn part1(map: &Map) {
-    println!("Part 1 got {} trees", map.count_slope(3, 1));
+fn part1_slope() -> Slope {
+    Slope::new(3, 1)
 }
 
-fn part2(map: &Map) {
-    let slopes = [(1, 1), (3, 1), (5, 1), (7, 1), (1, 2)];
-    let product: usize = slopes.iter().map(|s| map.count_slope(s.0, s.1)).product();
+fn part2_slopes() -> Vec<Slope> {
+    [(1, 1), (3, 1), (5, 1), (7, 1), (1, 2)]
+        .iter()
+        .map(|s| Slope::new(s.0, s.1))
+        .collect()
+}
+
+fn part1(trees: usize) {
+    println!("Part 1 got {} trees", trees);
+}
+
+fn part2(counts: &[SlopeCount]) {
+    let product: usize = counts.iter().map(|c| c.tally.get(Terrain::Tree)).product();
     println!("Part 2 product is {}", product);
 }
 
+fn print_slope(count: &SlopeCount, costs: &Costs) {
+    println!(
+        "Slope {} hits {}, costing {}",
+        count.slope,
+        count.tally,
+        count.tally.cost(costs)
+    );
+}
+
+fn print_search(result: Option<SearchResult>) {
+    match result {
+        Some(result) => {
+            println!("Searched {} slopes", result.searched);
+            for (label, c) in [("Fewest", result.fewest), ("Most", result.most)].iter() {
+                println!(
+                    "{} trees: slope {} hits {} trees over {} squares",
+                    label,
+                    c.slope,
+                    c.tally.get(Terrain::Tree),
+                    c.tally.squares()
+                );
+            }
+        }
+        None => println!("No slopes to search"),
+    }
+}
+
+struct Options {
+    slopes: Vec<Slope>,
+    toroidal: bool,
+    search: Option<SearchRange>,
+    render: bool,
+    color: bool,
+    output: Option<String>,
+    stream: bool,
+    route: bool,
+    moves: Vec<Move>,
+    legend: Legend,
+    costs: Costs,
+}
+
+// Usage: d3 [--slope right,down]... [--toroidal] [--search right_range,down_range]
+//           [--route [--move right,down]...]
+//           [--render [--color] [--output file]] [--stream]
+//           [--legend char=terrain]... [--cost terrain=cost]...
+//
+// Without slopes this solves the puzzle. Each --slope counts the trees on that
+// slope instead, and --toroidal wraps the map vertically too and counts the
+// trees for one full cycle. --search tries every integer slope in the given
+// ranges, e.g. "-7..7,1..3", and reports the best and the worst one, skipping
+// slopes that land on the same squares as one tried before.
+// --route finds the cheapest way down from anywhere on the top row, moving
+// down, down-left or down-right unless other moves are given with --move.
+// --render draws the route or the trajectories of the slopes (or of the
+// part 1 slope) over the map, in colour with --color, or into a file with --output.
+// --stream counts the slopes while the map is being read instead of loading
+// it first, for maps too big to keep in memory.
+//
+// Maps may hold trees, rocks and ice on top of open snow, drawn as '#', '@'
+// and '~' unless --legend says otherwise; other characters are an error.
+// Slopes report how many squares of each they land on, and what that costs
+// with the weights from --cost (trees 1, rocks 2, ice and open snow 0 by
+// default), which is also what --route minimizes.
+fn parse_args() -> anyhow::Result<Options> {
+    let mut options = Options {
+        slopes: Vec::new(),
+        toroidal: false,
+        search: None,
+        render: false,
+        color: false,
+        output: None,
+        stream: false,
+        route: false,
+        moves: Vec::new(),
+        legend: Legend::default(),
+        costs: Costs::default(),
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--slope" => {
+                let slope = args.next().ok_or_else(|| anyhow!("--slope needs a value"))?;
+                options.slopes.push(slope.parse()?);
+            }
+            "--toroidal" => options.toroidal = true,
+            "--search" => {
+                let range = args.next().ok_or_else(|| anyhow!("--search needs a value"))?;
+                options.search = Some(range.parse()?);
+            }
+            "--render" => options.render = true,
+            "--stream" => options.stream = true,
+            "--route" => options.route = true,
+            "--legend" => {
+                let entry = args.next().ok_or_else(|| anyhow!("--legend needs a value"))?;
+                options.legend.add_entry(&entry)?;
+            }
+            "--cost" => {
+                let entry = args.next().ok_or_else(|| anyhow!("--cost needs a value"))?;
+                options.costs.add_entry(&entry)?;
+            }
+            "--move" => {
+                let step = args.next().ok_or_else(|| anyhow!("--move needs a value"))?;
+                options.moves.push(step.parse()?);
+            }
+            "--color" => options.color = true,
+            "--output" => {
+                let path = args.next().ok_or_else(|| anyhow!("--output needs a file name"))?;
+                options.output = Some(path);
+            }
+            _ => return Err(anyhow!("Unknown argument: {}", arg)),
+        }
+    }
+    if options.toroidal && options.slopes.is_empty() {
+        return Err(anyhow!("--toroidal needs at least one --slope"));
+    }
+    if (options.color || options.output.is_some()) && !options.render {
+        return Err(anyhow!("--color and --output only apply to --render"));
+    }
+    if !options.moves.is_empty() && !options.route {
+        return Err(anyhow!("--move only applies to --route"));
+    }
+    if options.route && options.moves.is_empty() {
+        options.moves = route::default_moves();
+    }
+    if options.stream && (options.toroidal || options.render || options.route) {
+        return Err(anyhow!(
+            "--toroidal, --route and --render need the whole map, they can't --stream"
+        ));
+    }
+    Ok(options)
+}
+
+// Answers what main would, counting every slope involved in a single pass
+// over the input.
+fn stream(options: &Options, input: impl BufRead) -> anyhow::Result<()> {
+    let puzzle = options.slopes.is_empty() && options.search.is_none();
+    let mut slopes = Vec::new();
+    if puzzle {
+        slopes.push(part1_slope());
+        slopes.extend(part2_slopes());
+    }
+    slopes.extend(&options.slopes);
+
+    let (counts, searched) =
+        sweep::count_stream(input, &options.legend, &slopes, options.search.as_ref())?;
+    let mut counts = counts.as_slice();
+    if puzzle {
+        part1(counts[0].tally.get(Terrain::Tree));
+        part2(&counts[1..6]);
+        counts = &counts[6..];
+    }
+    if options.search.is_some() {
+        print_search(sweep::pick(&searched));
+    }
+    for c in counts {
+        print_slope(c, &options.costs);
+    }
+    Ok(())
+}
+
 fn main() -> anyhow::Result<()> {
-    let map = Map::parse(std::io::stdin().lock().lines())?;
-    part1(&map);
-    part2(&map);
+    let options = parse_args()?;
+    if options.stream {
+        return stream(&options, std::io::stdin().lock());
+    }
+    let map = Map::parse(std::io::stdin().lock().lines(), options.legend.clone())?;
+    if options.toroidal && map.height == 0 {
+        return Err(anyhow!("Can't wrap around an empty map"));
+    }
+    let route = if options.route {
+        let route = route::find_route(&map, &options.moves, &options.costs)
+            .ok_or_else(|| anyhow!("No route reaches the bottom row"))?;
+        Some(route)
+    } else {
+        None
+    };
+    if options.render {
+        if map.height == 0 {
+            return Err(anyhow!("Can't render an empty map"));
+        }
+        let mut paths: Vec<_> = route.iter().map(|r| r.squares.clone()).collect();
+        for &slope in &options.slopes {
+            paths.push(map.trajectory(slope)?);
+        }
+        if paths.is_empty() {
+            paths.push(map.trajectory(part1_slope())?);
+        }
+        match &options.output {
+            Some(path) => {
+                let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+                render::render(&map, &paths, options.color, &mut file)?;
+                file.flush()?;
+            }
+            None => render::render(&map, &paths, options.color, &mut std::io::stdout().lock())?,
+        }
+    }
+    if let Some(route) = &route {
+        println!(
+            "Best route starts in column {} and hits {}, costing {}",
+            route.squares[0].0, route.tally, route.cost
+        );
+        let moves: Vec<String> = route.moves.iter().map(Move::to_string).collect();
+        println!("Moves: {}", moves.join(" "));
+    }
+    if options.slopes.is_empty() && options.search.is_none() && !options.route {
+        part1(map.count_slope(part1_slope())?.get(Terrain::Tree));
+        part2(&map.count_slopes(&part2_slopes())?);
+    }
+    if let Some(range) = &options.search {
+        print_search(sweep::search(&map, range)?);
+    }
+    for slope in options.slopes {
+        if options.toroidal {
+            let cycle = map.count_toroidal(slope)?;
+            println!(
+                "Slope {} cycles after {} steps and hits {} trees per cycle",
+                slope, cycle.steps, cycle.trees
+            );
+        } else {
+            let tally = map.count_slope(slope)?;
+            print_slope(&SlopeCount { slope, tally }, &options.costs);
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,52 @@
+use crate::terrain::Terrain;
+use crate::Map;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+const COLORS: [u8; 6] = [31, 32, 33, 34, 35, 36];
+
+// Draws the map the way the puzzle statement does: the pattern repeated
+// sideways as far as the paths go, with 'O' where a path lands on open snow
+// and 'X' where it hits anything else. With `color` each path gets its own ANSI
+// colour; where paths cross, the one listed first wins.
+pub fn render(
+    map: &Map,
+    paths: &[Vec<(i64, usize)>],
+    color: bool,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let mut marks: HashMap<(i64, usize), usize> = HashMap::new();
+    let mut min_x = 0;
+    let mut max_x = 0;
+    for (i, path) in paths.iter().enumerate() {
+        for &(x, y) in path {
+            marks.entry((x, y)).or_insert(i);
+            min_x = min_x.min(x);
+            max_x = max_x.max(x);
+        }
+    }
+
+    // Always draw whole copies of the pattern.
+    let width = map.width as i64;
+    let left = min_x.div_euclid(width) * width;
+    let right = (max_x.div_euclid(width) + 1) * width;
+
+    for y in 0..map.height {
+        for x in left..right {
+            let terrain = map.terrain(x, y);
+            match marks.get(&(x, y)) {
+                Some(&i) => {
+                    let c = if terrain == Terrain::Open { 'O' } else { 'X' };
+                    if color {
+                        write!(out, "\x1b[1;{}m{}\x1b[0m", COLORS[i % COLORS.len()], c)?;
+                    } else {
+                        write!(out, "{}", c)?;
+                    }
+                }
+                None => write!(out, "{}", map.legend.symbol(terrain))?,
+            }
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
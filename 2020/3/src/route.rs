@@ -0,0 +1,141 @@
+use crate::terrain::{Costs, Tally};
+use crate::Map;
+use anyhow::anyhow;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fmt;
+use std::str::FromStr;
+
+// One step of a route: `right` columns (negative goes left) and `down` rows.
+// Unlike slopes, moves may stay on the same row.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Move {
+    pub right: i64,
+    pub down: usize,
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{}", self.right, self.down)
+    }
+}
+
+// Parses "right,down", e.g. "-1,1".
+impl FromStr for Move {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Move> {
+        let (right, down) = s
+            .split_once(',')
+            .ok_or_else(|| anyhow!("Invalid move, expecting right,down: {}", s))?;
+        let step = Move {
+            right: right.parse()?,
+            down: down.parse()?,
+        };
+        if step.right == 0 && step.down == 0 {
+            return Err(anyhow!("Moves need to go somewhere, got {}", s));
+        }
+        Ok(step)
+    }
+}
+
+// Down, down-left and down-right.
+pub fn default_moves() -> Vec<Move> {
+    [(0, 1), (-1, 1), (1, 1)]
+        .iter()
+        .map(|&(right, down)| Move { right, down })
+        .collect()
+}
+
+// The squares a route visits, starting on the top row, with columns not
+// wrapped so it can be drawn the same way as a slope.
+pub struct Route {
+    pub moves: Vec<Move>,
+    pub squares: Vec<(i64, usize)>,
+    pub tally: Tally,
+    pub cost: u64,
+}
+
+// Finds the cheapest route from any square of the top row to any square of
+// the bottom row, where every square landed on, the start included, costs
+// what `costs` says for its terrain. The map wraps sideways, so squares are
+// searched by their column within the pattern, with Dijkstra's algorithm.
+// Returns None when no route reaches the bottom row.
+pub fn find_route(map: &Map, moves: &[Move], costs: &Costs) -> Option<Route> {
+    if map.height == 0 || map.width == 0 {
+        return None;
+    }
+    let width = map.width;
+    let index = |x: usize, y: usize| y * width + x;
+    let mut best = vec![u64::MAX; width * map.height];
+    // The move that reached each square on its best route so far.
+    let mut came_by: Vec<Option<usize>> = vec![None; width * map.height];
+    let mut queue = BinaryHeap::new();
+
+    for x in 0..width {
+        let start_cost = costs.get(map.row(0).get(x));
+        best[index(x, 0)] = start_cost;
+        queue.push(Reverse((start_cost, x, 0)));
+    }
+    while let Some(Reverse((cost, x, y))) = queue.pop() {
+        if cost > best[index(x, y)] {
+            continue;
+        }
+        if y + 1 == map.height {
+            return Some(trace(map, moves, &came_by, x, y, cost));
+        }
+        for (i, step) in moves.iter().enumerate() {
+            let next_y = y + step.down;
+            if next_y >= map.height {
+                continue;
+            }
+            let next_x = (x as i64 + step.right).rem_euclid(width as i64) as usize;
+            let next_cost = cost + costs.get(map.row(next_y).get(next_x));
+            let next = index(next_x, next_y);
+            if next_cost < best[next] {
+                best[next] = next_cost;
+                came_by[next] = Some(i);
+                queue.push(Reverse((next_cost, next_x, next_y)));
+            }
+        }
+    }
+    None
+}
+
+// Walks the recorded moves back from (x, y) to the start, then replays them
+// forwards to get unwrapped columns.
+fn trace(
+    map: &Map,
+    moves: &[Move],
+    came_by: &[Option<usize>],
+    mut x: usize,
+    mut y: usize,
+    cost: u64,
+) -> Route {
+    let width = map.width as i64;
+    let mut taken = Vec::new();
+    while let Some(i) = came_by[y * map.width + x] {
+        let step = moves[i];
+        taken.push(step);
+        x = (x as i64 - step.right).rem_euclid(width) as usize;
+        y -= step.down;
+    }
+    taken.reverse();
+
+    let mut square = (x as i64, 0);
+    let mut squares = vec![square];
+    for step in &taken {
+        square = (square.0 + step.right, square.1 + step.down);
+        squares.push(square);
+    }
+    let mut tally = Tally::default();
+    for &(x, y) in &squares {
+        tally.add(map.terrain(x, y));
+    }
+    Route {
+        moves: taken,
+        squares,
+        tally,
+        cost,
+    }
+}
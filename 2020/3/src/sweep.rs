@@ -0,0 +1,313 @@
+use crate::bits::{self, Row};
+use crate::terrain::{Legend, Tally, Terrain, TerrainRow};
+use crate::{wrap_add, Map, Slope};
+use anyhow::anyhow;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+// What a slope landed on on the way down.
+#[derive(Copy, Clone, Debug)]
+pub struct SlopeCount {
+    pub slope: Slope,
+    pub tally: Tally,
+}
+
+// Follows many slopes at once, one row at a time. Slopes are grouped by their
+// `down` step and each group waits for the next row it lands on, so a row is
+// only looked at by the slopes that land on it and the whole map is walked
+// once no matter how many slopes there are.
+pub struct SlopeCounter {
+    counts: Vec<SlopeCount>,
+    // How many of `counts` were given to `new`. The rest come from `search`.
+    given: usize,
+    // The column of each slope and how far it moves per step, in units of
+    // 1 / right_den within one copy of the pattern as in `Map::count_slope`.
+    columns: Vec<(i64, i64)>,
+    // The width of the pattern in those units.
+    spans: Vec<i64>,
+    // Indices into `counts` grouped by their `down` step.
+    by_down: HashMap<usize, Vec<usize>>,
+    // The `down` steps of the groups that land on each row still to come.
+    due: HashMap<usize, Vec<usize>>,
+    search: Option<SearchRange>,
+    // What the top left square holds, once the first row is in.
+    origin: Option<Terrain>,
+    // The columns the slopes land on in the current row, packed like a row,
+    // and what `TerrainRow::query` found there.
+    mask: Vec<u64>,
+    found: [Vec<u64>; 3],
+    width: usize,
+    rows: usize,
+}
+
+impl SlopeCounter {
+    // Fails if some slope is too fine to track on a map `width` columns wide.
+    pub fn new(slopes: &[Slope], width: usize) -> anyhow::Result<SlopeCounter> {
+        let mut counter = SlopeCounter {
+            counts: Vec::new(),
+            given: slopes.len(),
+            columns: Vec::new(),
+            spans: Vec::new(),
+            by_down: HashMap::new(),
+            due: HashMap::new(),
+            search: None,
+            origin: None,
+            mask: vec![0; bits::words_for(width)],
+            found: Default::default(),
+            width,
+            rows: 0,
+        };
+        for &slope in slopes {
+            counter.add(slope, slope.span(width)?);
+        }
+        Ok(counter)
+    }
+
+    // Also counts every slope in `range`, which has to be set before the
+    // first row. Only slopes that can land on different squares are kept:
+    // rights a whole width apart and downs past the bottom row go the same
+    // way as the first of them, and each search slope is only added once
+    // the first row it lands on after the top one comes in.
+    pub fn search(&mut self, range: &SearchRange) {
+        debug_assert_eq!(self.rows, 0);
+        self.search = Some(range.clone());
+    }
+
+    // Starts following `slope`. A slope added after the first row has to
+    // land on the next row, or later, so the top left square is all it
+    // missed.
+    fn add(&mut self, slope: Slope, span: i64) {
+        // An empty map has nowhere to move to.
+        let step = slope.right.checked_rem_euclid(span).unwrap_or(0);
+        let mut count = SlopeCount {
+            slope,
+            tally: Tally::default(),
+        };
+        let (column, next) = match self.origin {
+            Some(origin) => {
+                debug_assert!(slope.down >= self.rows);
+                count.tally.add(origin);
+                (step, slope.down)
+            }
+            None => (0, 0),
+        };
+        self.counts.push(count);
+        self.columns.push((column, step));
+        self.spans.push(span);
+        let group = self.by_down.entry(slope.down).or_default();
+        if group.is_empty() {
+            self.due.entry(next).or_default().push(slope.down);
+        }
+        group.push(self.counts.len() - 1);
+    }
+
+    // Adds the searched slopes going `down` rows per step.
+    fn add_search(&mut self, down: usize) {
+        let rights = match &self.search {
+            Some(range) => range.rights(self.width),
+            None => return,
+        };
+        for right in rights {
+            self.add(Slope::new(right, down), self.width as i64);
+        }
+    }
+
+    // Feeds the next row of the map.
+    pub fn add_row(&mut self, row: TerrainRow<'_>) {
+        let y = self.rows;
+        if y == 0 {
+            self.origin = Some(row.get(0));
+        } else if self.search.as_ref().is_some_and(|r| r.down.contains(&y)) {
+            self.add_search(y);
+        }
+        self.rows += 1;
+        let downs = match self.due.remove(&y) {
+            Some(downs) => downs,
+            None => return,
+        };
+        // Which of the slopes landing on this row hit what is a single query
+        // over the columns they land on, and a row where none of them holds
+        // an obstacle is settled by that query alone.
+        for down in &downs {
+            for &i in &self.by_down[down] {
+                let x = self.columns[i].0 / self.counts[i].slope.right_den;
+                bits::set(&mut self.mask, x as usize);
+            }
+        }
+        let any = row.query(&self.mask, &mut self.found);
+        let (planes, width) = (&self.found, self.width);
+        let plane = |i: usize| Row::new(&planes[i], width);
+        let found = TerrainRow::new([plane(0), plane(1), plane(2)]);
+        for down in downs {
+            for &i in &self.by_down[&down] {
+                let count = &mut self.counts[i];
+                let (column, step) = &mut self.columns[i];
+                let x = (*column / count.slope.right_den) as usize;
+                bits::clear(&mut self.mask, x);
+                count
+                    .tally
+                    .add(if any { found.get(x) } else { Terrain::Open });
+                *column = wrap_add(*column, *step, self.spans[i]);
+            }
+            if let Some(next) = y.checked_add(down) {
+                self.due.entry(next).or_default().push(down);
+            }
+        }
+    }
+
+    // The counts of the slopes given to `new`, then those of the searched
+    // slopes in the order `SearchRange` lists them.
+    pub fn finish(mut self) -> (Vec<SlopeCount>, Vec<SlopeCount>) {
+        // The downs that never made it past the top row all land on the top
+        // left square alone, which the first of them stands for.
+        if let Some(range) = &self.search {
+            let down = self.rows.max(*range.down.start());
+            if down <= *range.down.end() {
+                self.add_search(down);
+            }
+        }
+        let searched = self.counts.split_off(self.given);
+        (self.counts, searched)
+    }
+}
+
+// Counts `slopes` and the slopes in `search` over a map read straight from
+// `input`, holding on to a single row at a time, so memory only grows with
+// the width of the map. Returns the counts as `SlopeCounter::finish` does.
+pub fn count_stream(
+    mut input: impl BufRead,
+    legend: &Legend,
+    slopes: &[Slope],
+    search: Option<&SearchRange>,
+) -> anyhow::Result<(Vec<SlopeCount>, Vec<SlopeCount>)> {
+    let mut counter: Option<SlopeCounter> = None;
+    let mut line = String::new();
+    let mut planes: [Vec<u64>; 3] = Default::default();
+    let mut width = None;
+    let mut y = 0;
+    loop {
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            break;
+        }
+        let trimmed = line.strip_suffix('\n').unwrap_or(&line);
+        let trimmed = trimmed.strip_suffix('\r').unwrap_or(trimmed);
+        legend
+            .check(trimmed)
+            .map_err(|e| anyhow!("Line {}: {}", y + 1, e))?;
+        if trimmed.is_empty() {
+            return Err(anyhow!("Line {}: the map can't have empty lines", y + 1));
+        }
+        if *width.get_or_insert(trimmed.len()) != trimmed.len() {
+            return Err(anyhow!("Expecting all input lines to have the same length"));
+        }
+        // The counter needs the width, which the first line sets.
+        let counter = match &mut counter {
+            Some(counter) => counter,
+            None => counter.get_or_insert(start_counter(slopes, search, trimmed.len())?),
+        };
+        planes.iter_mut().for_each(Vec::clear);
+        legend.pack(trimmed, &mut planes);
+        let plane = |i: usize| Row::new(&planes[i], trimmed.len());
+        counter.add_row(TerrainRow::new([plane(0), plane(1), plane(2)]));
+        y += 1;
+    }
+    match counter {
+        Some(counter) => Ok(counter.finish()),
+        None => Ok(start_counter(slopes, search, 0)?.finish()),
+    }
+}
+
+fn start_counter(
+    slopes: &[Slope],
+    search: Option<&SearchRange>,
+    width: usize,
+) -> anyhow::Result<SlopeCounter> {
+    let mut counter = SlopeCounter::new(slopes, width)?;
+    if let Some(range) = search {
+        counter.search(range);
+    }
+    Ok(counter)
+}
+
+// All integer slopes with `right` and `down` in the given inclusive ranges,
+// written as "right_min..right_max,down_min..down_max", e.g. "-7..7,1..3".
+#[derive(Clone)]
+pub struct SearchRange {
+    right: RangeInclusive<i64>,
+    down: RangeInclusive<usize>,
+}
+
+fn parse_range<T: FromStr>(s: &str) -> anyhow::Result<RangeInclusive<T>>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    let (start, end) = s
+        .split_once("..")
+        .ok_or_else(|| anyhow!("Invalid range, expecting min..max: {}", s))?;
+    Ok(start.parse()?..=end.parse()?)
+}
+
+impl FromStr for SearchRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<SearchRange> {
+        let (right, down) = s
+            .split_once(',')
+            .ok_or_else(|| anyhow!("Invalid search range, expecting right,down: {}", s))?;
+        let range = SearchRange {
+            right: parse_range(right)?,
+            down: parse_range(down)?,
+        };
+        if *range.down.start() == 0 {
+            return Err(anyhow!("Slopes need to move down, got {}", s));
+        }
+        Ok(range)
+    }
+}
+
+impl SearchRange {
+    // The rights worth trying on a map `width` columns wide, since a right
+    // that goes a whole width further lands on the same squares.
+    fn rights(&self, width: usize) -> RangeInclusive<i64> {
+        let start = *self.right.start();
+        let last = start.saturating_add(width.max(1) as i64 - 1);
+        start..=last.min(*self.right.end())
+    }
+}
+
+// The slopes hitting the fewest and the most trees. Ties go to the slope
+// visiting fewer squares, then to the one listed first.
+pub struct SearchResult {
+    pub fewest: SlopeCount,
+    pub most: SlopeCount,
+    pub searched: usize,
+}
+
+pub fn search(map: &Map, range: &SearchRange) -> anyhow::Result<Option<SearchResult>> {
+    let mut counter = SlopeCounter::new(&[], map.width)?;
+    counter.search(range);
+    for y in 0..map.height {
+        counter.add_row(map.row(y));
+    }
+    Ok(pick(&counter.finish().1))
+}
+
+// Picks the extremes out of counts that were already taken.
+pub fn pick(counts: &[SlopeCount]) -> Option<SearchResult> {
+    let trees = |c: &SlopeCount| c.tally.get(Terrain::Tree);
+    let fewest = *counts
+        .iter()
+        .min_by_key(|c| (trees(c), c.tally.squares()))?;
+    let most = *counts
+        .iter()
+        .min_by_key(|c| (Reverse(trees(c)), c.tally.squares()))?;
+    Some(SearchResult {
+        fewest,
+        most,
+        searched: counts.len(),
+    })
+}
@@ -0,0 +1,217 @@
+use crate::bits::{self, Row};
+use anyhow::anyhow;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Terrain {
+    Open,
+    Tree,
+    Rock,
+    Ice,
+}
+
+impl Terrain {
+    pub const ALL: [Terrain; 4] = [Terrain::Open, Terrain::Tree, Terrain::Rock, Terrain::Ice];
+    // Everything but open snow. Each of these gets its own bit plane.
+    pub const OBSTACLES: [Terrain; 3] = [Terrain::Tree, Terrain::Rock, Terrain::Ice];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Terrain::Open => "open",
+            Terrain::Tree => "tree",
+            Terrain::Rock => "rock",
+            Terrain::Ice => "ice",
+        }
+    }
+}
+
+impl FromStr for Terrain {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Terrain> {
+        Terrain::ALL
+            .iter()
+            .copied()
+            .find(|t| t.name() == s)
+            .ok_or_else(|| anyhow!("Unknown terrain {:?}, expecting open, tree, rock or ice", s))
+    }
+}
+
+// Which character stands for which terrain on the map. Characters that
+// aren't in the legend are rejected rather than taken as open snow.
+#[derive(Clone, Debug)]
+pub struct Legend {
+    chars: Vec<(char, Terrain)>,
+}
+
+impl Default for Legend {
+    fn default() -> Legend {
+        Legend {
+            chars: vec![
+                ('.', Terrain::Open),
+                ('#', Terrain::Tree),
+                ('@', Terrain::Rock),
+                ('~', Terrain::Ice),
+            ],
+        }
+    }
+}
+
+impl Legend {
+    pub fn terrain(&self, c: char) -> Option<Terrain> {
+        self.chars.iter().find(|(k, _)| *k == c).map(|&(_, t)| t)
+    }
+
+    // The character drawn for `terrain`, the first one listed for it.
+    pub fn symbol(&self, terrain: Terrain) -> char {
+        self.chars
+            .iter()
+            .find(|(_, t)| *t == terrain)
+            .map_or('?', |&(c, _)| c)
+    }
+
+    // Adds or replaces an entry written as "char=terrain", e.g. "T=tree".
+    // Characters have to be ASCII so that columns and bytes line up.
+    pub fn add_entry(&mut self, s: &str) -> anyhow::Result<()> {
+        let (c, terrain) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Invalid legend entry, expecting char=terrain: {}", s))?;
+        let mut chars = c.chars();
+        let c = match (chars.next(), chars.next()) {
+            (Some(c), None) if c.is_ascii() && !c.is_ascii_whitespace() => c,
+            _ => {
+                return Err(anyhow!(
+                    "Legend entries need a single ASCII character: {}",
+                    s
+                ))
+            }
+        };
+        let terrain = terrain.parse()?;
+        self.chars.retain(|(k, _)| *k != c);
+        self.chars.push((c, terrain));
+        Ok(())
+    }
+
+    // Checks that every character of `line` is in the legend.
+    pub fn check(&self, line: &str) -> Result<(), String> {
+        match line.chars().position(|c| self.terrain(c).is_none()) {
+            Some(column) => Err(format!(
+                "unknown terrain {:?} in column {}",
+                line.chars().nth(column).unwrap(),
+                column + 1
+            )),
+            None => Ok(()),
+        }
+    }
+
+    // Appends the bit planes of a checked `line` to `planes`, one per
+    // obstacle.
+    pub fn pack(&self, line: &str, planes: &mut [Vec<u64>; 3]) {
+        for (plane, &terrain) in planes.iter_mut().zip(Terrain::OBSTACLES.iter()) {
+            bits::pack(line, plane, |c| self.terrain(c) == Some(terrain));
+        }
+    }
+}
+
+// The cost of landing on each terrain, for weighted totals and routes.
+#[derive(Copy, Clone, Debug)]
+pub struct Costs([u64; 4]);
+
+// Trees cost 1 like in the puzzle, rocks are worse and ice is free.
+impl Default for Costs {
+    fn default() -> Costs {
+        Costs([0, 1, 2, 0])
+    }
+}
+
+impl Costs {
+    pub fn get(&self, terrain: Terrain) -> u64 {
+        self.0[terrain as usize]
+    }
+
+    // Sets a cost written as "terrain=cost", e.g. "rock=5".
+    pub fn add_entry(&mut self, s: &str) -> anyhow::Result<()> {
+        let (terrain, cost) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Invalid cost, expecting terrain=cost: {}", s))?;
+        let terrain: Terrain = terrain.parse()?;
+        self.0[terrain as usize] = cost.parse()?;
+        Ok(())
+    }
+}
+
+// How many squares of each terrain something landed on.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Tally([usize; 4]);
+
+impl Tally {
+    pub fn add(&mut self, terrain: Terrain) {
+        self.0[terrain as usize] += 1;
+    }
+
+    pub fn get(&self, terrain: Terrain) -> usize {
+        self.0[terrain as usize]
+    }
+
+    pub fn squares(&self) -> usize {
+        self.0.iter().sum()
+    }
+
+    pub fn cost(&self, costs: &Costs) -> u64 {
+        Terrain::ALL
+            .iter()
+            .map(|&t| self.get(t) as u64 * costs.get(t))
+            .sum()
+    }
+}
+
+impl fmt::Display for Tally {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} trees, {} rocks, {} ice over {} squares",
+            self.get(Terrain::Tree),
+            self.get(Terrain::Rock),
+            self.get(Terrain::Ice),
+            self.squares()
+        )
+    }
+}
+
+// One row of the map as a bit plane per obstacle.
+#[derive(Copy, Clone)]
+pub struct TerrainRow<'a> {
+    planes: [Row<'a>; 3],
+}
+
+impl<'a> TerrainRow<'a> {
+    pub fn new(planes: [Row<'a>; 3]) -> TerrainRow<'a> {
+        TerrainRow { planes }
+    }
+
+    pub fn get(&self, x: usize) -> Terrain {
+        for (plane, &terrain) in self.planes.iter().zip(Terrain::OBSTACLES.iter()) {
+            if plane.get(x) {
+                return terrain;
+            }
+        }
+        Terrain::Open
+    }
+
+    // Looks up all the columns set in `mask`, packed like the planes, at
+    // once: `found` gets those holding each obstacle, as planes of their own.
+    // Returns false when none of them holds any, so they are all open.
+    pub fn query(&self, mask: &[u64], found: &mut [Vec<u64>; 3]) -> bool {
+        let mut any = false;
+        for (plane, found) in self.planes.iter().zip(found.iter_mut()) {
+            any |= plane.and(mask, found);
+        }
+        any
+    }
+
+    // Like `get`, with the pattern repeating in both directions.
+    pub fn get_wrapped(&self, x: i64) -> Terrain {
+        self.get(self.planes[0].wrap(x))
+    }
+}
@@ -0,0 +1,143 @@
+use anyhow::anyhow;
+use std::str::FromStr;
+
+// The two letters that halve a range: the first keeps the lower half, the
+// second the upper one.
+#[derive(Copy, Clone, Debug)]
+pub struct Letters {
+    pub low: char,
+    pub high: char,
+}
+
+impl Letters {
+    // Which half `c` picks, or None when it's neither letter.
+    pub fn step(&self, c: char) -> Option<bool> {
+        if c == self.low {
+            Some(false)
+        } else if c == self.high {
+            Some(true)
+        } else {
+            None
+        }
+    }
+
+    pub fn letter(&self, high: bool) -> char {
+        if high {
+            self.high
+        } else {
+            self.low
+        }
+    }
+}
+
+impl FromStr for Letters {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Letters> {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next(), chars.next()) {
+            (Some(low), Some(high), None) if low != high => Ok(Letters { low, high }),
+            _ => Err(anyhow!("Expecting two different letters, got {:?}", s)),
+        }
+    }
+}
+
+// How passes map to seats on a plane: `row_bits` steps for the row and then
+// `column_bits` for the column, each with its own letters, and seat ids
+// computed as row * row_factor + column.
+#[derive(Copy, Clone, Debug)]
+pub struct Layout {
+    pub row_bits: u32,
+    pub column_bits: u32,
+    pub row_letters: Letters,
+    pub column_letters: Letters,
+    pub row_factor: u32,
+}
+
+// The puzzle's plane: 128 rows of 8 seats.
+impl Default for Layout {
+    fn default() -> Layout {
+        Layout {
+            row_bits: 7,
+            column_bits: 3,
+            row_letters: Letters {
+                low: 'F',
+                high: 'B',
+            },
+            column_letters: Letters {
+                low: 'L',
+                high: 'R',
+            },
+            row_factor: 8,
+        }
+    }
+}
+
+impl Layout {
+    pub fn rows(&self) -> u32 {
+        1 << self.row_bits
+    }
+
+    pub fn columns(&self) -> u32 {
+        1 << self.column_bits
+    }
+
+    pub fn pass_len(&self) -> usize {
+        (self.row_bits + self.column_bits) as usize
+    }
+
+    pub fn id(&self, row: u32, column: u32) -> u32 {
+        row * self.row_factor + column
+    }
+
+    // The row and column of seat `id`, if the plane has such a seat.
+    pub fn seat(&self, id: u32) -> Option<(u32, u32)> {
+        let (row, column) = (id / self.row_factor, id % self.row_factor);
+        if row < self.rows() && column < self.columns() {
+            Some((row, column))
+        } else {
+            None
+        }
+    }
+}
+
+// Parses comma separated "key=value" settings on top of the default layout,
+// e.g. "row_bits=6,column_bits=2,row_letters=UD,column_letters=<>,row_factor=20".
+// The row factor defaults to the number of columns.
+impl FromStr for Layout {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Layout> {
+        let mut layout = Layout::default();
+        let mut row_factor = None;
+        for setting in s.split(',') {
+            let (key, value) = setting.split_once('=').ok_or_else(|| {
+                anyhow!("Invalid layout setting, expecting key=value: {}", setting)
+            })?;
+            match key {
+                "row_bits" => layout.row_bits = value.parse()?,
+                "column_bits" => layout.column_bits = value.parse()?,
+                "row_letters" => layout.row_letters = value.parse()?,
+                "column_letters" => layout.column_letters = value.parse()?,
+                "row_factor" => row_factor = Some(value.parse()?),
+                _ => return Err(anyhow!("Unknown layout setting: {}", key)),
+            }
+        }
+        if layout.row_bits + layout.column_bits > 31 {
+            return Err(anyhow!("Layouts can have at most 31 steps"));
+        }
+        layout.row_factor = row_factor.unwrap_or_else(|| layout.columns());
+        if layout.row_factor < layout.columns() {
+            return Err(anyhow!(
+                "A row factor of {} would give seats in different rows the same id",
+                layout.row_factor
+            ));
+        }
+        let last = u64::from(layout.rows() - 1) * u64::from(layout.row_factor)
+            + u64::from(layout.columns() - 1);
+        if last > u64::from(u32::MAX) {
+            return Err(anyhow!("Seat ids don't fit in 32 bits with this layout"));
+        }
+        Ok(layout)
+    }
+}
@@ -1,57 +1,254 @@
 use anyhow::anyhow;
+use layout::Layout;
+use manifest::Manifest;
 use std::io::BufRead;
 use std::ops::Range;
 
-fn bsp(range: Range<u32>, steps: &mut dyn Iterator<Item = bool>) -> anyhow::Result<Range<u32>> {
+mod layout;
+mod manifest;
+
+// Halves `range` once per step until a single number is left. Running out
+// of steps before that, or having steps left over, is an error.
+fn bsp(range: Range<u32>, steps: &mut dyn Iterator<Item = bool>) -> anyhow::Result<u32> {
     let mut l = range.start;
     let mut h = range.end;
-    while l <= h {
-        let go_high = match steps.next() {
-            Some(dir) => dir,
-            None => return Ok(l..h),
-        };
-        let mid = (l + h) / 2;
+    while h - l > 1 {
+        let go_high = steps
+            .next()
+            .ok_or_else(|| anyhow!("Ran out of steps with {}..{} left", l, h))?;
+        let mid = l + (h - l) / 2;
         if go_high {
             l = mid;
         } else {
             h = mid;
         }
     }
-    Ok(l..h)
+    if steps.next().is_some() {
+        return Err(anyhow!("Steps left over after narrowing down to {}", l));
+    }
+    Ok(l)
+}
+
+// Maps each character of a pass to the half it picks, checking it against
+// the letters for its position.
+fn steps(line: &str, layout: &Layout) -> anyhow::Result<Vec<bool>> {
+    let mut steps = Vec::with_capacity(layout.pass_len());
+    for (i, c) in line.chars().enumerate() {
+        let letters = if i < layout.row_bits as usize {
+            layout.row_letters
+        } else {
+            layout.column_letters
+        };
+        let step = letters.step(c).ok_or_else(|| {
+            anyhow!(
+                "Unexpected {:?} at position {}, expecting {} or {}",
+                c,
+                i + 1,
+                letters.low,
+                letters.high
+            )
+        })?;
+        steps.push(step);
+    }
+    Ok(steps)
+}
+
+// The row and column of the seat on a pass, reading the steps as the bits of
+// the row and column numbers. The pass has to have the length and the letters
+// `layout` asks for.
+fn decode(line: &str, layout: &Layout) -> anyhow::Result<(u32, u32)> {
+    let len = line.chars().count();
+    if len != layout.pass_len() {
+        return Err(anyhow!(
+            "Expecting {} characters, got {}",
+            layout.pass_len(),
+            len
+        ));
+    }
+    let steps = steps(line, layout)?;
+    let (row_steps, column_steps) = steps.split_at(layout.row_bits as usize);
+    let number = |bits: &[bool]| bits.iter().fold(0, |n, &bit| n << 1 | bit as u32);
+    Ok((number(row_steps), number(column_steps)))
+}
+
+// Same as `decode`, following the puzzle's description by narrowing down the
+// rows and the columns step by step. Too few or too many steps show up as
+// narrowing errors rather than length errors.
+fn decode_bsp(line: &str, layout: &Layout) -> anyhow::Result<(u32, u32)> {
+    let steps = steps(line, layout)?;
+    let (row_steps, column_steps) = steps.split_at(steps.len().min(layout.row_bits as usize));
+    let row = bsp(0..layout.rows(), &mut row_steps.iter().copied())
+        .map_err(|e| anyhow!("Rows: {}", e))?;
+    let column = bsp(0..layout.columns(), &mut column_steps.iter().copied())
+        .map_err(|e| anyhow!("Columns: {}", e))?;
+    Ok((row, column))
+}
+
+// Decodes every pass both ways and makes sure the two agree.
+fn verify(lines: &[String], layout: &Layout) -> anyhow::Result<()> {
+    for (line_no, line) in (1..).zip(lines) {
+        let context = |e: anyhow::Error| anyhow!("Line {}: {}: {}", line_no, line, e);
+        let narrowed = decode_bsp(line, layout).map_err(context)?;
+        let read = decode(line, layout).map_err(context)?;
+        if narrowed != read {
+            return Err(anyhow!(
+                "Line {}: {}: narrowing gives row {}, column {} but the bits say row {}, column {}",
+                line_no,
+                line,
+                narrowed.0,
+                narrowed.1,
+                read.0,
+                read.1
+            ));
+        }
+    }
+    Ok(())
+}
+
+// The inverse of `decode`: the pass for the seat in `row` and `column`.
+fn encode(row: u32, column: u32, layout: &Layout) -> anyhow::Result<String> {
+    if row >= layout.rows() || column >= layout.columns() {
+        return Err(anyhow!("No seat at row {}, column {}", row, column));
+    }
+    let row_steps = (0..layout.row_bits)
+        .rev()
+        .map(|bit| layout.row_letters.letter(row >> bit & 1 == 1));
+    let column_steps = (0..layout.column_bits)
+        .rev()
+        .map(|bit| layout.column_letters.letter(column >> bit & 1 == 1));
+    Ok(row_steps.chain(column_steps).collect())
 }
 
-fn seat(line: &str) -> anyhow::Result<u32> {
-    if line.len() != 10 {
-        return Err(anyhow!("Invalid line length"));
+fn encode_id(id: u32, layout: &Layout) -> anyhow::Result<String> {
+    let (row, column) = layout
+        .seat(id)
+        .ok_or_else(|| anyhow!("No seat has id {}", id))?;
+    encode(row, column, layout)
+}
+
+// Turns a seat id, or a "row,column" pair, back into a pass.
+fn encode_line(line: &str, layout: &Layout) -> anyhow::Result<String> {
+    match line.split_once(',') {
+        Some((row, column)) => encode(row.trim().parse()?, column.trim().parse()?, layout),
+        None => encode_id(line.trim().parse()?, layout),
     }
-    let mut row_steps = line.chars().take(7).map(|c| c == 'B');
-    let mut seat_steps = line.chars().skip(7).map(|c| c == 'R');
+}
 
-    let row = bsp(0..128, &mut row_steps)?;
-    let seat = bsp(0..8, &mut seat_steps)?;
-    let id = row.start * 8 + seat.start;
+// The rows and columns of all passes, pointing at the offending line on
+// errors.
+fn decode_all(lines: &[String], layout: &Layout) -> anyhow::Result<Vec<(u32, u32)>> {
+    lines
+        .iter()
+        .enumerate()
+        .map(|(line_no, line)| {
+            decode(line, layout).map_err(|e| anyhow!("Line {}: {}: {}", line_no + 1, line, e))
+        })
+        .collect()
+}
 
-    Ok(id)
+fn seat_ids(lines: &[String], layout: &Layout) -> anyhow::Result<Vec<u32>> {
+    let seats = decode_all(lines, layout)?;
+    Ok(seats
+        .iter()
+        .map(|&(row, column)| layout.id(row, column))
+        .collect())
 }
 
-fn part1(lines: &Vec<String>) -> u32 {
-    lines.iter().map(|l| seat(l).unwrap()).max().unwrap()
+// Checks that every pass decodes to a seat that encodes back to the same
+// pass.
+fn check(lines: &[String], layout: &Layout) -> anyhow::Result<()> {
+    let ids = seat_ids(lines, layout)?;
+    for (line_no, (line, &id)) in lines.iter().zip(&ids).enumerate() {
+        let encoded = encode_id(id, layout)?;
+        if encoded != *line {
+            return Err(anyhow!(
+                "Line {}: {} decodes to seat {} which encodes back as {}",
+                line_no + 1,
+                line,
+                id,
+                encoded
+            ));
+        }
+    }
+    Ok(())
 }
 
-fn part2(lines: &Vec<String>) -> u32 {
-    let mut ids: Vec<u32> = lines.iter().map(|l| seat(l).unwrap()).collect();
-    ids.sort();
-    let slice = ids.windows(2).find(|w| w[1] - w[0] == 2).unwrap();
-    slice[0] + 1
+fn part1(ids: &[u32]) -> Option<u32> {
+    ids.iter().copied().max()
 }
 
+// The first empty seat whose neighbours by id are both taken. With a
+// `row_factor` above the number of columns some ids belong to no seat, and
+// those don't count.
+fn part2(ids: &[u32], layout: &Layout) -> Option<u32> {
+    let mut ids = ids.to_vec();
+    ids.sort_unstable();
+    let slice = ids
+        .windows(2)
+        .find(|w| w[1] - w[0] == 2 && layout.seat(w[0] + 1).is_some())?;
+    Some(slice[0] + 1)
+}
+
+// Usage: day5 [--layout settings] [--encode | --check | --verify | --report]
+//
+// --layout describes a plane other than the puzzle's, see `Layout`.
+// --encode reads seat ids or "row,column" pairs instead of passes and prints
+// the pass for each. --check makes sure every pass in the manifest survives a
+// round trip through its seat id. --verify decodes every pass both bit by bit
+// and by narrowing down ranges, and fails unless the two agree. --report draws
+// the plane and lists its empty seats and duplicate passes.
 fn main() -> anyhow::Result<()> {
+    let mut layout = Layout::default();
+    let mut encode = false;
+    let mut check_only = false;
+    let mut report = false;
+    let mut verify_only = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--layout" => {
+                let settings = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--layout needs a value"))?;
+                layout = settings.parse()?;
+            }
+            "--encode" => encode = true,
+            "--check" => check_only = true,
+            "--report" => report = true,
+            "--verify" => verify_only = true,
+            _ => return Err(anyhow!("Unknown argument: {}", arg)),
+        }
+    }
+
     let lines: std::result::Result<Vec<String>, std::io::Error> =
         std::io::stdin().lock().lines().collect();
     let lines = lines?;
-    let max = part1(&lines);
-    let id = part2(&lines);
+    if encode {
+        for line in &lines {
+            println!("{}", encode_line(line, &layout)?);
+        }
+        return Ok(());
+    }
+    if check_only {
+        check(&lines, &layout)?;
+        println!("All {} passes round-trip", lines.len());
+        return Ok(());
+    }
+    if verify_only {
+        verify(&lines, &layout)?;
+        println!("Both decoders agree on all {} passes", lines.len());
+        return Ok(());
+    }
+    if report {
+        let manifest = Manifest::new(layout, &decode_all(&lines, &layout)?)?;
+        manifest.report(&mut std::io::stdout().lock())?;
+        return Ok(());
+    }
+    let ids = seat_ids(&lines, &layout)?;
+    let max = part1(&ids).ok_or_else(|| anyhow!("The manifest is empty"))?;
     println!("Part 1 max: {}", max);
-    println!("Part 2 id: {}", id);
+    let id =
+        part2(&ids, &layout).ok_or_else(|| anyhow!("No empty seat has both neighbours taken"))?;
+    println!("Part 2 id: {} ({})", id, encode_id(id, &layout)?);
     Ok(())
 }
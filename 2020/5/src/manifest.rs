@@ -0,0 +1,193 @@
+use crate::encode;
+use crate::layout::Layout;
+use anyhow::anyhow;
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
+
+// Manifests go up to 2^MAX_SEAT_BITS seats. They hold a list for every seat
+// and the report draws every one of them, which stops making sense well
+// before the biggest layouts.
+const MAX_SEAT_BITS: usize = 20;
+
+// Who sits where on a plane, from a list of decoded passes.
+pub struct Manifest {
+    layout: Layout,
+    // Line numbers of the passes for each seat, row after row.
+    passes: Vec<Vec<usize>>,
+}
+
+// Where an empty seat is relative to the occupied ones. Seats before the
+// first or after the last occupied one are taken not to exist on this plane.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Gap {
+    Front,
+    Interior,
+    Back,
+}
+
+impl Manifest {
+    // `seats` holds the row and column of each pass, in manifest order.
+    pub fn new(layout: Layout, seats: &[(u32, u32)]) -> anyhow::Result<Manifest> {
+        if layout.pass_len() > MAX_SEAT_BITS {
+            return Err(anyhow!(
+                "The layout has {} seats, manifests only go up to {}",
+                1u64 << layout.pass_len(),
+                1 << MAX_SEAT_BITS
+            ));
+        }
+        let mut passes = vec![Vec::new(); (layout.rows() * layout.columns()) as usize];
+        for (line_no, &(row, column)) in (1..).zip(seats) {
+            passes[(row * layout.columns() + column) as usize].push(line_no);
+        }
+        Ok(Manifest { layout, passes })
+    }
+
+    fn position(&self, index: usize) -> (u32, u32) {
+        let index = index as u32;
+        (index / self.layout.columns(), index % self.layout.columns())
+    }
+
+    fn is_taken(&self, id: u32) -> bool {
+        match self.layout.seat(id) {
+            Some((row, column)) => {
+                !self.passes[(row * self.layout.columns() + column) as usize].is_empty()
+            }
+            None => false,
+        }
+    }
+
+    // The row and column of every empty seat, with where it is. Without any
+    // passes there are no occupied seats to place gaps against, so there are
+    // none.
+    pub fn empty_seats(&self) -> Vec<(u32, u32, Gap)> {
+        let first = self.passes.iter().position(|p| !p.is_empty());
+        let last = self.passes.iter().rposition(|p| !p.is_empty());
+        let mut seats = Vec::new();
+        if first.is_none() {
+            return seats;
+        }
+        for (index, passes) in self.passes.iter().enumerate() {
+            if !passes.is_empty() {
+                continue;
+            }
+            let gap = match (first, last) {
+                (Some(first), _) if index < first => Gap::Front,
+                (_, Some(last)) if index < last => Gap::Interior,
+                _ => Gap::Back,
+            };
+            let (row, column) = self.position(index);
+            seats.push((row, column, gap));
+        }
+        seats
+    }
+
+    // Seats with more than one pass, along with the lines of those passes.
+    pub fn duplicates(&self) -> Vec<(u32, u32, &[usize])> {
+        self.passes
+            .iter()
+            .enumerate()
+            .filter(|(_, passes)| passes.len() > 1)
+            .map(|(index, passes)| {
+                let (row, column) = self.position(index);
+                (row, column, passes.as_slice())
+            })
+            .collect()
+    }
+
+    // Whether the seats with the ids right before and after the given one
+    // are taken, which is how the puzzle finds its seat.
+    pub fn has_neighbours(&self, row: u32, column: u32) -> bool {
+        let id = self.layout.id(row, column);
+        let taken = |id: Option<u32>| id.is_some_and(|id| self.is_taken(id));
+        taken(id.checked_sub(1)) && taken(id.checked_add(1))
+    }
+
+    // Draws the plane a row per line, '#' for a taken seat, '.' for an empty
+    // one and '!' for a seat with several passes, then lists the empty seats
+    // and the duplicates.
+    pub fn report(&self, out: &mut impl Write) -> io::Result<()> {
+        if self.passes.iter().all(Vec::is_empty) {
+            return writeln!(out, "No passes");
+        }
+        let label_width = (self.layout.rows() - 1).to_string().len();
+        for (row, seats) in self
+            .passes
+            .chunks(self.layout.columns() as usize)
+            .enumerate()
+        {
+            let seats: String = seats
+                .iter()
+                .map(|passes| match passes.len() {
+                    0 => '.',
+                    1 => '#',
+                    _ => '!',
+                })
+                .collect();
+            writeln!(out, "Row {:>width$} {}", row, seats, width = label_width)?;
+        }
+
+        let taken = self.passes.iter().filter(|p| !p.is_empty()).count();
+        writeln!(out, "Taken: {} of {} seats", taken, self.passes.len())?;
+        let empty = self.empty_seats();
+        for &(gap, label) in [(Gap::Front, "front"), (Gap::Back, "back")].iter() {
+            let ids: Vec<u32> = empty
+                .iter()
+                .filter(|s| s.2 == gap)
+                .map(|&(row, column, _)| self.layout.id(row, column))
+                .collect();
+            write!(out, "Missing at the {}: {} seats", label, ids.len())?;
+            if !ids.is_empty() {
+                let runs: Vec<String> = runs(&ids)
+                    .iter()
+                    .map(|run| format!("{}-{}", run.start(), run.end()))
+                    .collect();
+                write!(out, ", ids {}", runs.join(", "))?;
+            }
+            writeln!(out)?;
+        }
+        let interior: Vec<_> = empty.iter().filter(|s| s.2 == Gap::Interior).collect();
+        writeln!(out, "Interior gaps: {}", interior.len())?;
+        for &&(row, column, _) in &interior {
+            let neighbours = if self.has_neighbours(row, column) {
+                ", both neighbours taken"
+            } else {
+                ""
+            };
+            writeln!(
+                out,
+                "  seat {} (row {}, column {}, pass {}){}",
+                self.layout.id(row, column),
+                row,
+                column,
+                encode(row, column, &self.layout).map_err(io::Error::other)?,
+                neighbours
+            )?;
+        }
+        let duplicates = self.duplicates();
+        writeln!(out, "Duplicate passes: {}", duplicates.len())?;
+        for (row, column, lines) in duplicates {
+            let lines: Vec<String> = lines.iter().map(usize::to_string).collect();
+            writeln!(
+                out,
+                "  seat {} (row {}, column {}) on lines {}",
+                self.layout.id(row, column),
+                row,
+                column,
+                lines.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Groups sorted ids into runs of consecutive ones.
+fn runs(ids: &[u32]) -> Vec<RangeInclusive<u32>> {
+    let mut runs: Vec<RangeInclusive<u32>> = Vec::new();
+    for &id in ids {
+        match runs.last_mut() {
+            Some(run) if *run.end() + 1 == id => *run = *run.start()..=id,
+            _ => runs.push(id..=id),
+        }
+    }
+    runs
+}
@@ -1,62 +1,133 @@
-use std::{collections::HashSet, default::Default, io::BufRead};
-
-fn build_groups<G, F>(
-    lines: impl Iterator<Item = String>,
-    mut grow_group: F,
-) -> impl Iterator<Item = G>
-where
-    F: FnMut(&mut G, &str),
-    G: Default,
-{
-    // Make sure there's an empty line at the end
-    let chained = lines.chain(std::iter::once(String::new()));
-    let mut current_group: G = Default::default();
-    chained.filter_map(move |line| {
-        if line.len() == 0 {
-            let mut result = Default::default();
-            std::mem::swap(&mut result, &mut current_group);
-            return Some(result);
+use anyhow::anyhow;
+use parsing::{RecordError, RecordReader};
+use query::Query;
+use stats::Stats;
+use std::io::BufRead;
+
+mod query;
+mod stats;
+
+// The questions one person answered yes to, bit 0 for 'a' up to bit 25 for
+// 'z'.
+fn answers(line: &str) -> Result<u32, String> {
+    line.chars().try_fold(0, |bits, c| {
+        if c.is_ascii_lowercase() {
+            Ok(bits | 1 << (c as u8 - b'a'))
+        } else {
+            Err(format!("unexpected {:?} in answers, expecting a to z", c))
         }
-        grow_group(&mut current_group, &line);
-        None
     })
 }
 
-fn part1(lines: impl Iterator<Item = String>) -> anyhow::Result<usize> {
-    let groups = build_groups(lines, |group: &mut HashSet<char>, line: &str| {
-        group.extend(line.chars());
-    });
-    Ok(groups.map(|set| set.len()).sum())
+// What a group answered: by anyone, by everyone, and how many people
+// answered each question.
+struct Group {
+    people: u32,
+    any: u32,
+    all: u32,
+    counts: [u32; 26],
 }
 
-#[derive(Default)]
-struct Group {
-    answers: HashSet<char>,
-    is_initialized: bool,
+impl Default for Group {
+    fn default() -> Group {
+        Group {
+            people: 0,
+            any: 0,
+            all: !0,
+            counts: [0; 26],
+        }
+    }
 }
 
-fn part2(lines: impl Iterator<Item = String>) -> anyhow::Result<usize> {
-    let groups = build_groups(lines, |group: &mut Group, line: &str| {
-        if group.is_initialized {
-            let person_answers: HashSet<char> = line.chars().collect();
-            let mut intersection: HashSet<char> =
-                group.answers.intersection(&person_answers).map(|c| c.clone()).collect();
-            std::mem::swap(&mut group.answers, &mut intersection);
-        } else {
-            group.answers.extend(line.chars());
-            group.is_initialized = true;
+impl Group {
+    fn add(&mut self, answers: u32) {
+        self.people += 1;
+        self.any |= answers;
+        self.all &= answers;
+        for (question, count) in self.counts.iter_mut().enumerate() {
+            *count += answers >> question & 1;
+        }
+    }
+
+    fn count(&self, query: Query) -> usize {
+        match query {
+            Query::Any => self.any.count_ones() as usize,
+            Query::All => self.all.count_ones() as usize,
+            _ => self
+                .counts
+                .iter()
+                .filter(|&&answered| query.matches(answered as usize, self.people as usize))
+                .count(),
         }
+    }
+}
+
+fn count(input: impl BufRead, query: Query) -> anyhow::Result<usize> {
+    let groups = RecordReader::new(input).fold_lines(|group: &mut Group, line: &str| {
+        group.add(answers(line)?);
+        Ok(())
     });
-    Ok(groups.map(|g| g.answers.len()).sum())
+    Ok(groups
+        .map(|g| g.map(|g| g.count(query)))
+        .sum::<Result<usize, RecordError>>()?)
+}
+
+// Same as `count`, but keeps track of where each group starts for the
+// report.
+fn stats(input: impl BufRead) -> anyhow::Result<Stats> {
+    let mut stats = Stats::default();
+    for record in RecordReader::new(input) {
+        let record = record?;
+        let mut group = Group::default();
+        for (line_no, line) in record.lines() {
+            let answers =
+                answers(line).map_err(|message| RecordError::Invalid { line_no, message })?;
+            group.add(answers);
+        }
+        stats.add(record.first_line(), &group);
+    }
+    Ok(stats)
 }
 
+// Usage: d6 [--part2 | --query query | --report [--csv]]
+//
+// Counts the questions anyone in a group answered, everyone did with
+// --part2, or those picked by --query, see `Query`. --report gives both
+// parts along with statistics per question and per group, as a table or as
+// CSV with --csv.
 fn main() -> anyhow::Result<()> {
+    let mut query = Query::Any;
+    let mut report = false;
+    let mut csv = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--part2" => query = Query::All,
+            "--query" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--query needs a value"))?;
+                query = value.parse()?;
+            }
+            "--report" => report = true,
+            "--csv" => csv = true,
+            _ => return Err(anyhow!("Unknown argument: {}", arg)),
+        }
+    }
     let input = std::io::stdin();
-    let lines = input.lock().lines().map(Result::unwrap);
-    if std::env::args().any(|arg| arg == "--part2") {
-        println!("Count: {}", part2(lines)?);
-    } else {
-        println!("Count: {}", part1(lines)?);
+    if report {
+        let stats = stats(input.lock())?;
+        let mut out = std::io::stdout().lock();
+        if csv {
+            stats.write_csv(&mut out)?;
+        } else {
+            stats.write_table(&mut out)?;
+        }
+        return Ok(());
+    }
+    if csv {
+        return Err(anyhow!("--csv only applies to --report"));
     }
+    println!("Count: {}", count(input.lock(), query)?);
     Ok(())
-}
\ No newline at end of file
+}
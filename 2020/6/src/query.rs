@@ -0,0 +1,79 @@
+use anyhow::anyhow;
+use std::str::FromStr;
+
+// Which questions of a group count, going by how many of its members
+// answered them. Questions nobody answered never count.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Query {
+    // Answered by anyone (part 1).
+    Any,
+    // Answered by everyone (part 2).
+    All,
+    AtLeast(usize),
+    Exactly(usize),
+    AtMost(usize),
+    // Answered by at least `numerator / denominator` of the group.
+    Fraction(usize, usize),
+}
+
+impl Query {
+    pub fn matches(&self, answered: usize, people: usize) -> bool {
+        if answered == 0 {
+            return false;
+        }
+        match *self {
+            Query::Any => true,
+            Query::All => answered == people,
+            Query::AtLeast(k) => answered >= k,
+            Query::Exactly(k) => answered == k,
+            Query::AtMost(k) => answered <= k,
+            Query::Fraction(numerator, denominator) => answered * denominator >= numerator * people,
+        }
+    }
+}
+
+// Parses "any", "all", "one" (answered by exactly one person), "at-least=K",
+// "exactly=K", "at-most=K" or "fraction=N/D".
+impl FromStr for Query {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Query> {
+        let (name, value) = match s.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (s, None),
+        };
+        let count = || -> anyhow::Result<usize> {
+            let value =
+                value.ok_or_else(|| anyhow!("Query {} needs a count, e.g. {}=2", name, name))?;
+            Ok(value.parse()?)
+        };
+        let query = match name {
+            "any" => Query::Any,
+            "all" => Query::All,
+            "one" => Query::Exactly(1),
+            "at-least" => Query::AtLeast(count()?),
+            "exactly" => Query::Exactly(count()?),
+            "at-most" => Query::AtMost(count()?),
+            "fraction" => {
+                let value = value
+                    .ok_or_else(|| anyhow!("Query fraction needs a value, e.g. fraction=2/3"))?;
+                let (numerator, denominator) = value
+                    .split_once('/')
+                    .ok_or_else(|| anyhow!("Invalid fraction, expecting N/D: {}", value))?;
+                let (numerator, denominator) = (numerator.parse()?, denominator.parse()?);
+                if denominator == 0 || numerator > denominator {
+                    return Err(anyhow!(
+                        "Fractions need to be between 0 and 1, got {}",
+                        value
+                    ));
+                }
+                Query::Fraction(numerator, denominator)
+            }
+            _ => return Err(anyhow!("Unknown query: {}", s)),
+        };
+        if value.is_some() && matches!(name, "any" | "all" | "one") {
+            return Err(anyhow!("Query {} doesn't take a value", name));
+        }
+        Ok(query)
+    }
+}
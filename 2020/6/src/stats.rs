@@ -0,0 +1,135 @@
+use crate::query::Query;
+use crate::Group;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+// Per question: groups where anyone answered it, groups where everyone did,
+// and people who did.
+#[derive(Copy, Clone, Default)]
+struct Question {
+    groups: usize,
+    unanimous: usize,
+    people: usize,
+}
+
+// Totals over every group of a declaration file.
+#[derive(Default)]
+pub struct Stats {
+    groups: usize,
+    people: usize,
+    part1: usize,
+    part2: usize,
+    questions: [Question; 26],
+    // Number of groups of each size.
+    sizes: BTreeMap<u32, usize>,
+    // First lines of the groups that agree on nothing.
+    no_unanimous: Vec<usize>,
+}
+
+fn letter(question: usize) -> char {
+    (b'a' + question as u8) as char
+}
+
+impl Stats {
+    pub fn add(&mut self, first_line: usize, group: &Group) {
+        self.groups += 1;
+        self.people += group.people as usize;
+        self.part1 += group.count(Query::Any);
+        self.part2 += group.count(Query::All);
+        for (i, question) in self.questions.iter_mut().enumerate() {
+            question.groups += (group.any >> i & 1) as usize;
+            question.unanimous += (group.all >> i & 1) as usize;
+            question.people += group.counts[i] as usize;
+        }
+        *self.sizes.entry(group.people).or_insert(0) += 1;
+        if group.all == 0 {
+            self.no_unanimous.push(first_line);
+        }
+    }
+
+    // The questions someone answered, with their letters.
+    fn answered(&self) -> impl Iterator<Item = (char, &Question)> {
+        self.questions
+            .iter()
+            .enumerate()
+            .filter(|(_, q)| q.people > 0)
+            .map(|(i, q)| (letter(i), q))
+    }
+
+    // The letters of the answered questions with the most or the fewest
+    // people, and that number of people.
+    fn extreme(&self, most: bool) -> Option<(String, usize)> {
+        let people = self.answered().map(|(_, q)| q.people);
+        let target = if most { people.max()? } else { people.min()? };
+        let letters = self
+            .answered()
+            .filter(|(_, q)| q.people == target)
+            .map(|(c, _)| c)
+            .collect();
+        Some((letters, target))
+    }
+
+    pub fn write_table(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "Part 1: {}", self.part1)?;
+        writeln!(out, "Part 2: {}", self.part2)?;
+        writeln!(out, "Groups: {}, people: {}", self.groups, self.people)?;
+        writeln!(out)?;
+        writeln!(out, "Question  Groups  Unanimous  People")?;
+        for (c, q) in self.answered() {
+            writeln!(
+                out,
+                "{:<8}  {:>6}  {:>9}  {:>6}",
+                c, q.groups, q.unanimous, q.people
+            )?;
+        }
+        for &(most, label) in [(true, "Most"), (false, "Least")].iter() {
+            if let Some((letters, people)) = self.extreme(most) {
+                writeln!(out, "{} common: {} ({} people)", label, letters, people)?;
+            }
+        }
+        writeln!(out)?;
+        writeln!(out, "Group size  Groups")?;
+        for (size, groups) in &self.sizes {
+            writeln!(out, "{:>10}  {:>6}", size, groups)?;
+        }
+        writeln!(out)?;
+        let lines: Vec<String> = self.no_unanimous.iter().map(usize::to_string).collect();
+        write!(
+            out,
+            "Groups with no unanimous answers: {}",
+            self.no_unanimous.len()
+        )?;
+        if !lines.is_empty() {
+            write!(out, ", starting on lines {}", lines.join(", "))?;
+        }
+        writeln!(out)
+    }
+
+    // The same as `write_table` as "section,key,value" rows.
+    pub fn write_csv(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "section,key,value")?;
+        writeln!(out, "total,part1,{}", self.part1)?;
+        writeln!(out, "total,part2,{}", self.part2)?;
+        writeln!(out, "total,groups,{}", self.groups)?;
+        writeln!(out, "total,people,{}", self.people)?;
+        for (c, q) in self.answered() {
+            writeln!(out, "question_groups,{},{}", c, q.groups)?;
+            writeln!(out, "question_unanimous,{},{}", c, q.unanimous)?;
+            writeln!(out, "question_people,{},{}", c, q.people)?;
+        }
+        for &(most, label) in [(true, "most_common"), (false, "least_common")].iter() {
+            if let Some((letters, people)) = self.extreme(most) {
+                for c in letters.chars() {
+                    writeln!(out, "{},{},{}", label, c, people)?;
+                }
+            }
+        }
+        for (size, groups) in &self.sizes {
+            writeln!(out, "group_size,{},{}", size, groups)?;
+        }
+        for line in &self.no_unanimous {
+            writeln!(out, "no_unanimous,line,{}", line)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+use crate::Graph;
+use digraph::{Direction, NodeId};
+use std::io::{self, Write};
+
+// Which part of the rules to export.
+pub enum Scope {
+    All,
+    // The colour and every colour it can hold.
+    From(NodeId),
+    // The colour and every colour that can hold it.
+    Reaching(NodeId),
+}
+
+// Colours and their direct contents, sorted by name so exports of similar
+// rule sets diff cleanly.
+fn adjacency<'g>(graph: &'g Graph, scope: &Scope) -> Vec<(&'g str, Vec<(&'g str, u32)>)> {
+    let reachable = |node, direction| {
+        let found = graph.rules.bfs(node, direction);
+        found.into_iter().map(|(n, _)| n).collect()
+    };
+    let nodes: Vec<NodeId> = match *scope {
+        Scope::All => graph.rules.nodes().collect(),
+        Scope::From(node) => reachable(node, Direction::Outgoing),
+        Scope::Reaching(node) => reachable(node, Direction::Incoming),
+    };
+    let mut included = vec![false; graph.rules.len()];
+    for node in &nodes {
+        included[node.index()] = true;
+    }
+    let name = |node: NodeId| graph.rules.key(node).as_str();
+    let mut adjacency: Vec<(&'g str, Vec<(&'g str, u32)>)> = nodes
+        .iter()
+        .map(|&node| {
+            let mut contents: Vec<(&'g str, u32)> = graph
+                .rules
+                .edges(node, Direction::Outgoing)
+                .iter()
+                .filter(|(to, _)| included[to.index()])
+                .map(|&(to, count)| (name(to), count))
+                .collect();
+            contents.sort_unstable();
+            (name(node), contents)
+        })
+        .collect();
+    adjacency.sort_unstable();
+    adjacency
+}
+
+// Quotes `s` as a string in both DOT and JSON.
+fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// Writes the rules as a Graphviz digraph with an edge from each bag to each
+// bag it holds, labelled with the count.
+pub fn write_dot(graph: &Graph, scope: &Scope, out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "digraph bags {{")?;
+    for (color, contents) in adjacency(graph, scope) {
+        if contents.is_empty() {
+            writeln!(out, "    {};", quote(color))?;
+        }
+        for (inner, count) in contents {
+            writeln!(
+                out,
+                "    {} -> {} [label=\"{}\"];",
+                quote(color),
+                quote(inner),
+                count
+            )?;
+        }
+    }
+    writeln!(out, "}}")
+}
+
+// Writes the rules as a JSON object mapping each colour to an object that
+// maps the colours it holds to their counts.
+pub fn write_json(graph: &Graph, scope: &Scope, out: &mut impl Write) -> io::Result<()> {
+    let adjacency = adjacency(graph, scope);
+    writeln!(out, "{{")?;
+    for (i, (color, contents)) in adjacency.iter().enumerate() {
+        let contents: Vec<String> = contents
+            .iter()
+            .map(|(inner, count)| format!("{}: {}", quote(inner), count))
+            .collect();
+        let separator = if i + 1 < adjacency.len() { "," } else { "" };
+        writeln!(
+            out,
+            "  {}: {{{}}}{}",
+            quote(color),
+            contents.join(", "),
+            separator
+        )?;
+    }
+    writeln!(out, "}}")
+}
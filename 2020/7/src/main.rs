@@ -1,151 +1,353 @@
-use hashbrown::HashMap;
-use hashbrown::HashSet;
-use lazy_static::lazy_static;
-use regex::Regex;
+use anyhow::{anyhow, Result};
+use digraph::{Cycle, Direction, Folded, Graph as DiGraph, GraphBuilder, Memo, NodeId};
+use export::Scope;
+use num_bigint::BigUint;
+use parsing::{keyword, parse_all, separated_list, tag, unsigned, word, ParseError, Parser};
 use std::io::BufRead;
 
-lazy_static! {
-    static ref RE_START: Regex = Regex::new(r"^(\w+ \w+) bags contain ").unwrap();
-    static ref RE_CONTAINED: Regex = Regex::new(r"(\d+) (\w+ \w+) bags?").unwrap();
-}
-
-#[derive(Copy, Clone)]
-enum EdgeDirection {
-    Contained,
-    Contains,
-}
+mod export;
+mod paths;
 
-struct Contains<'a> {
+// One input line: the colour of the outer bag, and the count and colour of
+// each bag it directly contains.
+struct Rule<'a> {
     container_color: &'a str,
-    contained_color: &'a str,
-    contained_count: u32,
+    contents: Vec<(u32, &'a str)>,
 }
 
+// Bag rules as a graph with an edge from each container colour to every colour
+// it directly contains, weighted by the count.
 struct Graph {
-    contains: HashMap<String, HashMap<String, u32>>,
-    contained_by: HashMap<String, HashMap<String, u32>>,
+    rules: DiGraph<String, u32>,
 }
 
-fn add_edge(edges: &mut HashMap<String, HashMap<String, u32>>, from: &str, to: &str, value: u32) {
-    let entry = edges
-        .raw_entry_mut()
-        .from_key(from)
-        .or_insert_with(|| (from.to_owned(), HashMap::new()));
-    entry.1.insert(to.to_owned(), value);
-}
+impl Graph {
+    // Reads one rule per line.
+    fn read(input: impl BufRead) -> Result<Graph> {
+        let mut rules = GraphBuilder::new();
+        for (i, line) in input.lines().enumerate() {
+            let line = line?;
+            let rule = parse_line(&line).map_err(|e| e.at_line(i + 1))?;
+            let container = rules.intern(rule.container_color);
+            for (count, color) in rule.contents {
+                let contained = rules.intern(color);
+                rules.add_edge(container, contained, count);
+            }
+        }
+        Ok(Graph {
+            rules: rules.build(),
+        })
+    }
 
-fn for_each_edge<'a, F>(edges: &'a HashMap<String, HashMap<String, u32>>, from: &str, mut f: F)
-where
-    F: FnMut(&'a String, u32),
-{
-    edges.get(from).map(move |edges| {
-        edges.iter().for_each(|kv| {
-            f(kv.0, *kv.1);
-        });
-    });
-}
+    // The node for `color`, which has to show up somewhere in the rules.
+    fn color(&self, color: &str) -> Result<NodeId> {
+        self.rules
+            .node(color)
+            .ok_or_else(|| anyhow!("No rule mentions {} bags", color))
+    }
 
-impl Graph {
-    fn new() -> Graph {
-        Graph {
-            contains: HashMap::new(),
-            contained_by: HashMap::new(),
+    // Every colour in the rules, sorted by name.
+    fn colors(&self) -> Vec<&str> {
+        let mut colors: Vec<&str> = self
+            .rules
+            .nodes()
+            .map(|n| self.rules.key(n).as_str())
+            .collect();
+        colors.sort_unstable();
+        colors
+    }
+
+    // Number of different bag colours that can eventually contain each of
+    // `nodes`. A search per colour is quicker for a few of them, but one count
+    // over the whole graph beats a search per colour for many.
+    fn count_containers(&self, nodes: &[NodeId]) -> Vec<usize> {
+        if nodes.len() <= 64 {
+            return nodes
+                .iter()
+                .map(|&node| self.rules.reaching(node).len())
+                .collect();
         }
+        let counts = self.rules.count_reaching();
+        nodes.iter().map(|node| counts[node.index()]).collect()
     }
 
-    fn add(&mut self, c: Contains) {
-        add_edge(
-            &mut self.contained_by,
-            c.contained_color,
-            c.container_color,
-            c.contained_count,
-        );
-        add_edge(
-            &mut self.contains,
-            c.container_color,
-            c.contained_color,
-            c.contained_count,
-        );
-    }
-
-    fn for_each_edge<'a, F>(&'a self, from: &str, direction: EdgeDirection, f: F)
-    where
-        F: FnMut(&'a String, u32),
-    {
-        let edges: &HashMap<_, _> = match direction {
-            EdgeDirection::Contained => &self.contained_by,
-            EdgeDirection::Contains => &self.contains,
-        };
-        for_each_edge(edges, from, f);
+    // A chain of colours written out as "a → b → c".
+    fn describe(&self, nodes: &[NodeId]) -> String {
+        let colors: Vec<&str> = nodes.iter().map(|&n| self.rules.key(n).as_str()).collect();
+        colors.join(" → ")
     }
 
-    fn dfs_post_order<'a, F>(&'a self, from: &'a str, direction: EdgeDirection, f: &mut F)
-    where
-        F: FnMut(&'a str),
-    {
-        let mut visited: HashSet<&str> = HashSet::new();
-        self.do_dfs_post_order(from, direction, &mut |n| {
-            if visited.insert(n) {
-                f(n);
+    // Why there's no count of the bags inside `node`'s colour.
+    fn circular(&self, node: NodeId, cycle: &Cycle) -> anyhow::Error {
+        anyhow!(
+            "Can't count the bags inside {} bags, the rules go round in circles: {}",
+            self.rules.key(node),
+            self.describe(&cycle.0)
+        )
+    }
+
+    // Folds the bag counts up from the bottom of the rules below `node`,
+    // which fails when the bag can end up inside itself.
+    fn fold_contents<T>(
+        &self,
+        node: NodeId,
+        f: impl FnMut(NodeId, &mut dyn Iterator<Item = (&u32, &T)>) -> T,
+    ) -> Result<Memo<T>> {
+        self.rules
+            .fold_dag(&[node], Direction::Outgoing, f)
+            .map_err(|cycle| self.circular(node, &cycle))
+    }
+
+    // Part 2 for every colour at once, with or without an upper limit.
+    fn totals(&self, big: bool) -> Totals {
+        if big {
+            Totals::Big(self.rules.fold_all(|_, contents| {
+                contents
+                    .map(|(&count, sub_sum)| (sub_sum + 1u32) * count)
+                    .sum()
+            }))
+        } else {
+            // None for the colours whose total overflows.
+            Totals::Checked(self.rules.fold_all::<Option<u32>, _>(|_, contents| {
+                contents
+                    .map(|(&count, &sub_sum)| sub_sum?.checked_add(1)?.checked_mul(count))
+                    .try_fold(0u32, |sum, bags| sum.checked_add(bags?))
+            }))
+        }
+    }
+
+    // Number of bags inside a single bag of `node`'s colour. There is no such
+    // number when the bag can end up inside itself, or when it doesn't fit in
+    // a u32 without --big.
+    fn total_contents(&self, totals: &Totals, node: NodeId) -> Result<String> {
+        let totals = match totals {
+            Totals::Big(totals) => {
+                let total = totals.get(node).map_err(|c| self.circular(node, c))?;
+                return Ok(total.to_string());
             }
-        });
+            Totals::Checked(totals) => totals,
+        };
+        if let Some(total) = totals.get(node).map_err(|c| self.circular(node, c))? {
+            return Ok(total.to_string());
+        }
+        // Follow the overflowing colours down to one whose contents all fit,
+        // which is where the count first overflows.
+        let mut path = vec![node];
+        while let Some(&(next, _)) = self
+            .rules
+            .edges(*path.last().unwrap(), Direction::Outgoing)
+            .iter()
+            .find(|(n, _)| totals.get(*n) == Ok(&None))
+        {
+            path.push(next);
+        }
+        let last = *path.last().unwrap();
+        Err(anyhow!(
+            "The bags inside {} bags don't fit in 32 bits, the count overflows along {} at {}, try --big",
+            self.rules.key(node),
+            self.describe(&path),
+            self.overflowing_edge(totals, last)
+        ))
     }
 
-    // Recursive because the iterative version is a pain in the ass to write.
-    fn do_dfs_post_order<'a, F>(&'a self, from: &'a str, direction: EdgeDirection, f: &mut F)
-    where
-        F: FnMut(&'a str),
-    {
-        self.for_each_edge(from, direction, |neighbour, _| {
-            self.do_dfs_post_order(neighbour, direction, f);
-        });
-        f(from);
+    // Replays the count for `node`, whose contents all fit, to describe the
+    // contents that make it overflow.
+    fn overflowing_edge(&self, totals: &Folded<Option<u32>>, node: NodeId) -> String {
+        // Every step of the count fits in a u64.
+        let limit = u64::from(u32::MAX);
+        let mut sum = 0u64;
+        for &(next, count) in self.rules.edges(node, Direction::Outgoing) {
+            let each = match totals.get(next) {
+                Ok(Some(sub_sum)) => u64::from(*sub_sum) + 1,
+                _ => continue,
+            };
+            let color = self.rules.key(next);
+            let bags = each * u64::from(count);
+            if bags > limit {
+                return format!("{} {} bags counting {} bags each", count, color, each);
+            }
+            if sum + bags > limit {
+                return format!(
+                    "{} {} bags counting {} bags in all on top of {} already counted",
+                    count, color, bags, sum
+                );
+            }
+            sum += bags;
+        }
+        format!("{} bags", sum)
     }
 }
 
-fn parse_line(line: &str) -> impl Iterator<Item = Contains> {
-    let start_cap = RE_START.captures(line).unwrap();
-    let container_color = start_cap.get(1).unwrap().as_str();
-    let contained_slice = &line[start_cap.get(0).unwrap().end()..];
-    let contained_iter = RE_CONTAINED.captures_iter(contained_slice);
-    contained_iter.map(move |cap| {
-        let contained_count: u32 = cap.get(1).unwrap().as_str().parse().unwrap();
-        let contained_color: &str = cap.get(2).unwrap().as_str();
-        Contains {
-            container_color,
-            contained_color,
-            contained_count,
-        }
-    })
+// The bags inside each colour, counted with u32s, None where those
+// overflow, or with BigUints.
+enum Totals {
+    Checked(Folded<Option<u32>>),
+    Big(Folded<BigUint>),
 }
 
-fn main() {
-    let mut graph = Graph::new();
-    std::io::stdin()
-        .lock()
-        .lines()
-        .map(Result::unwrap)
-        .for_each(|l| {
-            parse_line(&l).for_each(|contains| graph.add(contains));
-        });
+fn color<'a>() -> impl Parser<'a, Output = &'a str> {
+    word().then(tag(" ")).then(word()).recognize()
+}
 
-    // part 1
-    let mut count = 0;
-    graph.dfs_post_order("shiny gold", EdgeDirection::Contained, &mut |_| {
-        count += 1;
-    });
-    // -1 because dfs post order includes the starting node
-    println!("Shiny gold bags can be contained in {} bags", count - 1);
-
-    // part 2
-    let mut totals: HashMap<&str, u32> = HashMap::new();
-    graph.dfs_post_order("shiny gold", EdgeDirection::Contains, &mut |n| {
-        let mut sum = 0;
-        graph.for_each_edge(n, EdgeDirection::Contains, |sub, count| {
-            let sub_sum = totals.get(sub.as_str()).map(Clone::clone).unwrap_or(0);
-            sum += (sub_sum + 1) * count;
+fn parse_line(line: &str) -> Result<Rule<'_>, ParseError> {
+    let bag = keyword("bags").or(keyword("bag"));
+    let content = unsigned::<u32>()
+        .then_ignore(tag(" "))
+        .then(color())
+        .then_ignore(tag(" "))
+        .then_ignore(bag);
+    let contents = separated_list(content, tag(", ")).or(tag("no other bags").map(|_| Vec::new()));
+    let rule = color()
+        .then_ignore(tag(" bags contain "))
+        .then(contents)
+        .then_ignore(tag("."))
+        .map(|(container_color, contents)| Rule {
+            container_color,
+            contents,
         });
-        totals.insert(n, sum);
-    });
-    println!("Shiny gold bags contain {} total bags", totals.get("shiny gold").unwrap());
+    parse_all(&rule, line)
+}
+
+// Prints both answers for `color`: part 1 and part 2 for "shiny gold".
+fn answer(
+    graph: &Graph,
+    totals: &Totals,
+    color: &str,
+    node: NodeId,
+    containers: usize,
+) -> Result<()> {
+    let mut chars = color.chars();
+    let name: String = chars
+        .next()
+        .into_iter()
+        .flat_map(char::to_uppercase)
+        .chain(chars)
+        .collect();
+    println!("{} bags can be contained in {} bags", name, containers);
+    let total = graph.total_contents(totals, node)?;
+    println!("{} bags contain {} total bags", name, total);
+    Ok(())
+}
+
+enum Export {
+    Dot,
+    Json,
+}
+
+// Usage: d7 [--color color]... [--all] [--big]
+//        d7 (--dot | --json) [--from color | --reaching color]
+//        d7 --paths inner outer
+//
+// Answers both parts for shiny gold bags, or for each colour given with
+// --color, which may also list several colours separated by commas, e.g.
+// --color "shiny gold,dark red". --all answers for every colour in the rules.
+// Part 2 fails when the count doesn't fit in 32 bits, unless --big counts
+// with arbitrary precision, or when the rules go round in circles. Either way
+// the other colours are still answered before d7 exits with an error.
+//
+// --dot and --json export the rules instead, as a Graphviz graph or as a
+// JSON object, limited to the bags that a colour can hold with --from, or to
+// the bags that can hold it with --reaching.
+//
+// --paths lists every way inner bags end up inside outer bags, with the
+// number of inner bags each way puts in an outer bag, and picks out the
+// shortest one and the one that puts in the most.
+fn main() -> Result<()> {
+    let mut colors = Vec::new();
+    let mut all = false;
+    let mut big = false;
+    let mut export = None;
+    let mut from = None;
+    let mut reaching = None;
+    let mut paths = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--color" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--color needs a value"))?;
+                colors.extend(value.split(',').map(|c| c.trim().to_owned()));
+            }
+            "--all" => all = true,
+            "--big" => big = true,
+            "--dot" => export = Some(Export::Dot),
+            "--json" => export = Some(Export::Json),
+            "--from" => from = Some(args.next().ok_or_else(|| anyhow!("--from needs a value"))?),
+            "--reaching" => {
+                reaching = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("--reaching needs a value"))?,
+                )
+            }
+            "--paths" => {
+                let mut color = || {
+                    args.next()
+                        .ok_or_else(|| anyhow!("--paths needs two colours"))
+                };
+                paths = Some((color()?, color()?));
+            }
+            _ => return Err(anyhow!("Unknown argument: {}", arg)),
+        }
+    }
+    if export.is_none() && (from.is_some() || reaching.is_some()) {
+        return Err(anyhow!(
+            "--from and --reaching only apply to --dot and --json"
+        ));
+    }
+    if from.is_some() && reaching.is_some() {
+        return Err(anyhow!("Only one of --from and --reaching can be given"));
+    }
+    if colors.is_empty() && !all {
+        colors.push("shiny gold".to_owned());
+    }
+
+    let graph = Graph::read(std::io::stdin().lock())?;
+
+    if let Some((inner, outer)) = paths {
+        let (inner, outer) = (graph.color(&inner)?, graph.color(&outer)?);
+        if inner == outer {
+            return Err(anyhow!("--paths needs two different colours"));
+        }
+        paths::write_paths(&graph, inner, outer, &mut std::io::stdout().lock())?;
+        return Ok(());
+    }
+
+    if let Some(export) = export {
+        let scope = match (&from, &reaching) {
+            (Some(color), _) => Scope::From(graph.color(color)?),
+            (_, Some(color)) => Scope::Reaching(graph.color(color)?),
+            _ => Scope::All,
+        };
+        let mut out = std::io::stdout().lock();
+        match export {
+            Export::Dot => export::write_dot(&graph, &scope, &mut out)?,
+            Export::Json => export::write_json(&graph, &scope, &mut out)?,
+        }
+        return Ok(());
+    }
+    if all {
+        colors.extend(graph.colors().into_iter().map(str::to_owned));
+    }
+    let nodes = colors
+        .iter()
+        .map(|color| graph.color(color))
+        .collect::<Result<Vec<_>>>()?;
+    let totals = graph.totals(big);
+    let containers = graph.count_containers(&nodes);
+    let mut failed = 0;
+    for ((color, &node), &containers) in colors.iter().zip(&nodes).zip(&containers) {
+        if let Err(e) = answer(&graph, &totals, color, node, containers) {
+            eprintln!("Error: {}", e);
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        return Err(anyhow!(
+            "No total for {} of {} colours",
+            failed,
+            colors.len()
+        ));
+    }
+    Ok(())
 }
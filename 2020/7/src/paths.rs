@@ -0,0 +1,172 @@
+use crate::Graph;
+use anyhow::Result;
+use digraph::{Direction, Memo, NodeId};
+use num_bigint::BigUint;
+use std::io::Write;
+
+// How a colour leads down to the inner colour of a query, through every path
+// between the two.
+struct Reach {
+    node: NodeId,
+    // Inner bags inside one bag of this colour, over all paths.
+    bags: BigUint,
+    paths: BigUint,
+    // Steps on the shortest path, and the next colour on it.
+    shortest: (usize, Option<NodeId>),
+    // Inner bags along the path that holds the most, and the next colour on
+    // it.
+    heaviest: (BigUint, Option<NodeId>),
+}
+
+// One step of the walk over every path, with the inner bags per outer bag so
+// far and the next edge to try.
+struct Frame {
+    node: NodeId,
+    bags: BigUint,
+    next: usize,
+}
+
+// How `node` leads down to the inner colour, if it does.
+fn reach(memo: &Memo<Option<Reach>>, node: NodeId) -> Option<&Reach> {
+    memo.get(node).and_then(Option::as_ref)
+}
+
+// Follows the next colours picked by `next` from `outer` down.
+fn follow(
+    memo: &Memo<Option<Reach>>,
+    outer: NodeId,
+    next: fn(&Reach) -> Option<NodeId>,
+) -> Vec<NodeId> {
+    let mut path = vec![outer];
+    while let Some(node) = reach(memo, *path.last().unwrap()).and_then(next) {
+        path.push(node);
+    }
+    path
+}
+
+// Writes every path by which `inner` bags end up inside `outer` bags with the
+// number of inner bags it puts in each outer one, then the shortest path and
+// the one that puts in the most bags.
+pub fn write_paths(
+    graph: &Graph,
+    inner: NodeId,
+    outer: NodeId,
+    out: &mut impl Write,
+) -> Result<()> {
+    let memo = graph.fold_contents(outer, |node, contents| {
+        if node == inner {
+            return Some(Reach {
+                node,
+                bags: BigUint::from(1u32),
+                paths: BigUint::from(1u32),
+                shortest: (0, None),
+                heaviest: (BigUint::from(1u32), None),
+            });
+        }
+        let mut reach: Option<Reach> = None;
+        for (&count, sub_reach) in contents {
+            let sub_reach = match sub_reach {
+                Some(sub_reach) => sub_reach,
+                None => continue,
+            };
+            let heaviest = &sub_reach.heaviest.0 * count;
+            let reach = reach.get_or_insert_with(|| Reach {
+                node,
+                bags: BigUint::default(),
+                paths: BigUint::default(),
+                shortest: (usize::MAX, None),
+                heaviest: (BigUint::default(), None),
+            });
+            reach.bags += &sub_reach.bags * count;
+            reach.paths += &sub_reach.paths;
+            if sub_reach.shortest.0 + 1 < reach.shortest.0 {
+                reach.shortest = (sub_reach.shortest.0 + 1, Some(sub_reach.node));
+            }
+            if heaviest > reach.heaviest.0 {
+                reach.heaviest = (heaviest, Some(sub_reach.node));
+            }
+        }
+        reach
+    })?;
+    let name = |node| graph.rules.key(node);
+    let total = match reach(&memo, outer) {
+        Some(total) => total,
+        None => {
+            writeln!(
+                out,
+                "No path puts {} bags inside {} bags",
+                name(inner),
+                name(outer)
+            )?;
+            return Ok(());
+        }
+    };
+    let paths = if total.paths == BigUint::from(1u32) {
+        "path puts"
+    } else {
+        "paths put"
+    };
+    writeln!(
+        out,
+        "{} {} {} {} bags inside a {} bag",
+        total.paths,
+        paths,
+        total.bags,
+        name(inner),
+        name(outer)
+    )?;
+
+    let mut stack = vec![Frame {
+        node: outer,
+        bags: BigUint::from(1u32),
+        next: 0,
+    }];
+    while let Some(frame) = stack.last_mut() {
+        if frame.node == inner {
+            let path: Vec<NodeId> = stack.iter().map(|f| f.node).collect();
+            writeln!(
+                out,
+                "  {}: {}",
+                stack.last().unwrap().bags,
+                graph.describe(&path)
+            )?;
+            stack.pop();
+            continue;
+        }
+        let edges = graph.rules.edges(frame.node, Direction::Outgoing);
+        match edges[frame.next..]
+            .iter()
+            .position(|&(to, _)| reach(&memo, to).is_some())
+        {
+            Some(offset) => {
+                let (to, count) = edges[frame.next + offset];
+                frame.next += offset + 1;
+                let bags = &frame.bags * count;
+                stack.push(Frame {
+                    node: to,
+                    bags,
+                    next: 0,
+                });
+            }
+            None => {
+                stack.pop();
+            }
+        }
+    }
+
+    let shortest = follow(&memo, outer, |r| r.shortest.1);
+    writeln!(
+        out,
+        "Shortest: {} ({} steps)",
+        graph.describe(&shortest),
+        total.shortest.0
+    )?;
+    let heaviest = follow(&memo, outer, |r| r.heaviest.1);
+    writeln!(
+        out,
+        "Most bags: {} ({} bags)",
+        graph.describe(&heaviest),
+        total.heaviest.0
+    )?;
+    Ok(())
+}
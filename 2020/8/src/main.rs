@@ -1,13 +1,9 @@
-use anyhow::{anyhow, Result};
-use lazy_static::lazy_static;
-use regex::Regex;
-use std::collections::{HashMap, HashSet};
+use anyhow::Result;
+use digraph::{Direction, Graph, GraphBuilder};
+use parsing::{keyword, parse_all, signed, tag, ParseError, Parser};
+use std::collections::HashSet;
 use std::io::BufRead;
 
-lazy_static! {
-    static ref OP_RE: Regex = Regex::new(r"(acc|jmp|nop) ((\+|-)\d+)?").unwrap();
-}
-
 #[derive(Copy, Clone)]
 enum Op {
     Acc(i32),
@@ -15,16 +11,16 @@ enum Op {
     Jmp(i32),
 }
 
-fn parse_line(line: &str) -> Result<Op> {
-    let err = || anyhow!("Invalid input line: {}", line);
-    let caps = OP_RE.captures(line).ok_or(err())?;
-    let arg = caps[2].parse::<i32>()?;
-    match &caps[1] {
-        "acc" => Ok(Op::Acc(arg)),
-        "nop" => Ok(Op::Nop(arg)),
-        "jmp" => Ok(Op::Jmp(arg)),
-        &_ => Err(err()),
-    }
+fn parse_line(line: &str) -> Result<Op, ParseError> {
+    let op = keyword("acc")
+        .map(|_| Op::Acc as fn(i32) -> Op)
+        .or(keyword("nop").map(|_| Op::Nop as fn(i32) -> Op))
+        .or(keyword("jmp").map(|_| Op::Jmp as fn(i32) -> Op));
+    let instruction = op
+        .then_ignore(tag(" "))
+        .then(signed::<i32>())
+        .map(|(op, arg)| op(arg));
+    parse_all(&instruction, line)
 }
 
 fn run_op(op: &Op, ip: &mut i32, acc: &mut i32) {
@@ -38,7 +34,7 @@ fn run_op(op: &Op, ip: &mut i32, acc: &mut i32) {
     }
 }
 
-fn last_acc_before_loop_or_end(ops: &Vec<Op>) -> i32 {
+fn last_acc_before_loop_or_end(ops: &[Op]) -> i32 {
     let mut acc = 0;
     let mut ip = 0;
     let mut executed: HashSet<i32> = HashSet::new();
@@ -55,51 +51,43 @@ fn last_acc_before_loop_or_end(ops: &Vec<Op>) -> i32 {
     }
 }
 
-fn jump_sources(ops: &Vec<Op>) -> HashMap<i32, Vec<i32>> {
-    let mut result = HashMap::new();
-    ops.iter().enumerate().for_each(|(i, op)| {
-        if let Op::Jmp(delta) = op {
-            let source = i as i32;
-            let target: i32 = source + delta;
-            result
-                .entry(target)
-                .or_insert_with(|| Vec::new())
-                .push(source);
-        }
-    });
-    result
-}
-
-fn is_jmp(op: &Op) -> bool {
+fn next_ip(op: &Op, ip: i32) -> i32 {
     match op {
-        Op::Jmp(_) => true,
-        _ => false,
+        Op::Jmp(delta) => ip + delta,
+        Op::Nop(_) | Op::Acc(_) => ip + 1,
     }
 }
 
-// This is basically a DFS in a graph that has instruction pointers as nodes. The
-// edges link instructions that can follow each other in the program flow.
-//
-// It returns the set of instruction pointers from which the program can halt.
-fn endings(ops: &Vec<Op>, jump_sources: &HashMap<i32, Vec<i32>>) -> HashSet<i32> {
-    let mut visited: HashSet<i32> = HashSet::new();
-    let mut leads: Vec<i32> = Vec::new();
-    leads.push(ops.len() as i32);
-    while let Some(target) = leads.pop() {
-        visited.insert(target);
-
-        // If the previous op is a nop or acc, it's an implicit source for the
-        // current location.
-        if target > 0 && !is_jmp(&ops[target as usize - 1]) {
-            leads.push(target - 1);
+// A graph that has instruction pointers as nodes. The edges link instructions
+// that can follow each other in the program flow. Node `ops.len()` stands for
+// the end of the program; jumps anywhere else outside the program are left out.
+fn flow_graph(ops: &[Op]) -> Graph<i32, ()> {
+    let mut graph = GraphBuilder::new();
+    let end = ops.len() as i32;
+    for ip in 0..=end {
+        graph.intern(&ip);
+    }
+    for (ip, op) in (0..).zip(ops.iter()) {
+        let target = next_ip(op, ip);
+        if (0..=end).contains(&target) {
+            let from = graph.intern(&ip);
+            let to = graph.intern(&target);
+            graph.add_edge(from, to, ());
         }
-
-        // Add the jumps that lead to this target.
-        jump_sources.get(&target).map(|sources| {
-            leads.extend(sources.iter());
-        });
     }
-    visited
+    graph.build()
+}
+
+// Returns the set of instruction pointers from which the program can halt,
+// which are the nodes that can reach the end in the flow graph.
+fn endings(ops: &[Op]) -> HashSet<i32> {
+    let graph = flow_graph(ops);
+    let end = graph.node(&(ops.len() as i32)).unwrap();
+    graph
+        .bfs(end, Direction::Incoming)
+        .into_iter()
+        .map(|(ip, _)| *graph.key(ip))
+        .collect()
 }
 
 fn flip_op(op: &Op) -> Op {
@@ -123,9 +111,8 @@ fn alternate_ip_offset(op: &Op) -> i32 {
 // reachable from the start are guaranteed to loop forever, so none of the
 // valid endings can include any of these instructions. Thus even after flipping
 // one of them the endings remain valid.
-fn find_corrupted_op(ops: &Vec<Op>) -> i32 {
-    let jump_sources = jump_sources(ops);
-    let endings = endings(ops, &jump_sources);
+fn find_corrupted_op(ops: &[Op]) -> i32 {
+    let endings = endings(ops);
     let mut acc = 0;
     let mut ip = 0;
     loop {
@@ -142,7 +129,8 @@ fn main() -> Result<()> {
     let ops: Result<Vec<Op>> = std::io::stdin()
         .lock()
         .lines()
-        .map(|l| parse_line(&l?))
+        .enumerate()
+        .map(|(i, l)| Ok(parse_line(&l?).map_err(|e| e.at_line(i + 1))?))
         .collect();
     let mut ops = ops?;
     println!(
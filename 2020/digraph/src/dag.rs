@@ -0,0 +1,495 @@
+use crate::{Direction, Graph, NodeId};
+
+// A closed path through the graph. The first node is repeated at the end, so
+// a self loop on `a` is `[a, a]`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cycle(pub Vec<NodeId>);
+
+// Per-node results of a fold. Only nodes the fold actually reached have a
+// value.
+pub struct Memo<T>(Vec<Option<T>>);
+
+impl<T> Memo<T> {
+    pub fn get(&self, node: NodeId) -> Option<&T> {
+        self.0[node.index()].as_ref()
+    }
+}
+
+// Per-node results of `fold_all`: a value, or the cycle that keeps the node
+// from having one.
+pub struct Folded<T> {
+    values: Vec<Result<T, usize>>,
+    cycles: Vec<Cycle>,
+}
+
+impl<T> Folded<T> {
+    pub fn get(&self, node: NodeId) -> Result<&T, &Cycle> {
+        match &self.values[node.index()] {
+            Ok(value) => Ok(value),
+            Err(cycle) => Err(&self.cycles[*cycle]),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Mark {
+    Unvisited,
+    Active,
+    Done,
+}
+
+impl<K, W> Graph<K, W> {
+    // Depth first walk from each of `starts` that calls `on_done` on each node
+    // after all of its neighbours are done. Running into a node that is still
+    // on the stack means there's a cycle, which is returned instead.
+    fn walk_acyclic<F>(
+        &self,
+        starts: &[NodeId],
+        direction: Direction,
+        mut on_done: F,
+    ) -> Result<(), Cycle>
+    where
+        F: FnMut(NodeId),
+    {
+        let mut marks = vec![Mark::Unvisited; self.len()];
+        let mut stack: Vec<(NodeId, usize)> = Vec::new();
+        for &start in starts {
+            if marks[start.index()] != Mark::Unvisited {
+                continue;
+            }
+            marks[start.index()] = Mark::Active;
+            stack.push((start, 0));
+            while let Some((node, next_edge)) = stack.last_mut() {
+                let node = *node;
+                match self.edges(node, direction).get(*next_edge) {
+                    Some((neighbour, _)) => {
+                        *next_edge += 1;
+                        match marks[neighbour.index()] {
+                            Mark::Unvisited => {
+                                marks[neighbour.index()] = Mark::Active;
+                                stack.push((*neighbour, 0));
+                            }
+                            Mark::Active => {
+                                let from = stack.iter().position(|(n, _)| n == neighbour).unwrap();
+                                let mut path: Vec<NodeId> =
+                                    stack[from..].iter().map(|(n, _)| *n).collect();
+                                path.push(*neighbour);
+                                return Err(Cycle(path));
+                            }
+                            Mark::Done => {}
+                        }
+                    }
+                    None => {
+                        stack.pop();
+                        marks[node.index()] = Mark::Done;
+                        on_done(node);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Finds any cycle in the graph, following outgoing edges.
+    pub fn find_cycle(&self) -> Option<Cycle> {
+        let all: Vec<NodeId> = self.nodes().collect();
+        self.walk_acyclic(&all, Direction::Outgoing, |_| {}).err()
+    }
+
+    // Orders all nodes so every edge goes from an earlier node to a later one.
+    pub fn topological_sort(&self) -> Result<Vec<NodeId>, Cycle> {
+        let all: Vec<NodeId> = self.nodes().collect();
+        let mut order: Vec<NodeId> = Vec::with_capacity(self.len());
+        self.walk_acyclic(&all, Direction::Outgoing, |n| order.push(n))?;
+        order.reverse();
+        Ok(order)
+    }
+
+    // Tarjan's algorithm, with an explicit stack instead of recursion.
+    // Components come out in reverse topological order: no edge leads from a
+    // component to one listed after it.
+    //
+    // See: https://en.wikipedia.org/wiki/Tarjan%27s_strongly_connected_components_algorithm
+    pub fn strongly_connected_components(&self) -> Vec<Vec<NodeId>> {
+        let mut components: Vec<Vec<NodeId>> = Vec::new();
+        let mut index: Vec<Option<usize>> = vec![None; self.len()];
+        let mut low_link: Vec<usize> = vec![0; self.len()];
+        let mut on_stack = vec![false; self.len()];
+        let mut stack: Vec<NodeId> = Vec::new();
+        let mut calls: Vec<(NodeId, usize)> = Vec::new();
+        let mut next_index = 0;
+
+        for root in self.nodes() {
+            if index[root.index()].is_some() {
+                continue;
+            }
+            calls.push((root, 0));
+            while let Some((node, next_edge)) = calls.last_mut() {
+                let node = *node;
+                if *next_edge == 0 && index[node.index()].is_none() {
+                    index[node.index()] = Some(next_index);
+                    low_link[node.index()] = next_index;
+                    next_index += 1;
+                    stack.push(node);
+                    on_stack[node.index()] = true;
+                }
+                match self.edges(node, Direction::Outgoing).get(*next_edge) {
+                    Some((neighbour, _)) => {
+                        *next_edge += 1;
+                        match index[neighbour.index()] {
+                            None => calls.push((*neighbour, 0)),
+                            Some(i) if on_stack[neighbour.index()] => {
+                                low_link[node.index()] = low_link[node.index()].min(i);
+                            }
+                            Some(_) => {}
+                        }
+                    }
+                    None => {
+                        calls.pop();
+                        if Some(low_link[node.index()]) == index[node.index()] {
+                            let mut component: Vec<NodeId> = Vec::new();
+                            while let Some(member) = stack.pop() {
+                                on_stack[member.index()] = false;
+                                component.push(member);
+                                if member == node {
+                                    break;
+                                }
+                            }
+                            components.push(component);
+                        }
+                        if let Some((parent, _)) = calls.last() {
+                            let low = low_link[node.index()];
+                            low_link[parent.index()] = low_link[parent.index()].min(low);
+                        }
+                    }
+                }
+            }
+        }
+        components
+    }
+
+    // Computes a value for every node reachable from `starts`, bottom up: `f`
+    // gets the node and an iterator over (edge weight, neighbour value) for
+    // all its neighbours, each of which is computed exactly once. Fails if a
+    // cycle is reachable, since there's no bottom to start from.
+    pub fn fold_dag<T, F>(
+        &self,
+        starts: &[NodeId],
+        direction: Direction,
+        mut f: F,
+    ) -> Result<Memo<T>, Cycle>
+    where
+        F: FnMut(NodeId, &mut dyn Iterator<Item = (&W, &T)>) -> T,
+    {
+        let mut memo: Vec<Option<T>> = Vec::new();
+        memo.resize_with(self.len(), || None);
+        self.walk_acyclic(starts, direction, |node| {
+            let value = {
+                let mut neighbours = self
+                    .edges(node, direction)
+                    .iter()
+                    .map(|(n, w)| (w, memo[n.index()].as_ref().unwrap()));
+                f(node, &mut neighbours)
+            };
+            memo[node.index()] = Some(value);
+        })?;
+        Ok(Memo(memo))
+    }
+
+    // Like `fold_dag` over every node along outgoing edges, except that a
+    // cycle only spoils the nodes that can reach it, which get the cycle
+    // instead of a value. Works through the strongly connected components,
+    // so the whole graph takes a single pass.
+    pub fn fold_all<T, F>(&self, mut f: F) -> Folded<T>
+    where
+        F: FnMut(NodeId, &mut dyn Iterator<Item = (&W, &T)>) -> T,
+    {
+        let components = self.strongly_connected_components();
+        let mut component_of = vec![0; self.len()];
+        for (i, component) in components.iter().enumerate() {
+            for node in component {
+                component_of[node.index()] = i;
+            }
+        }
+        let mut values: Vec<Option<Result<T, usize>>> = Vec::new();
+        values.resize_with(self.len(), || None);
+        let mut cycles = Vec::new();
+        // Where each node sits on the walk looking for a cycle, if it's on it.
+        let mut on_walk = vec![usize::MAX; self.len()];
+        for (i, component) in components.iter().enumerate() {
+            let node = component[0];
+            let edges = self.edges(node, Direction::Outgoing);
+            if component.len() > 1 || edges.iter().any(|(n, _)| *n == node) {
+                // Every node of the component has an edge to another one, so
+                // following those edges has to come back to a node already
+                // on the walk.
+                let mut walk = Vec::new();
+                let mut current = node;
+                while on_walk[current.index()] == usize::MAX {
+                    on_walk[current.index()] = walk.len();
+                    walk.push(current);
+                    current = self
+                        .edges(current, Direction::Outgoing)
+                        .iter()
+                        .map(|(n, _)| *n)
+                        .find(|n| component_of[n.index()] == i)
+                        .unwrap();
+                }
+                let mut cycle = walk[on_walk[current.index()]..].to_vec();
+                cycle.push(current);
+                for n in &walk {
+                    on_walk[n.index()] = usize::MAX;
+                }
+                for n in component {
+                    values[n.index()] = Some(Err(cycles.len()));
+                }
+                cycles.push(Cycle(cycle));
+                continue;
+            }
+            let value = {
+                let neighbours = edges
+                    .iter()
+                    .map(|(n, w)| (w, values[n.index()].as_ref().unwrap()));
+                let spoiled = neighbours.clone().find_map(|(_, v)| v.as_ref().err());
+                match spoiled {
+                    Some(&cycle) => Err(cycle),
+                    None => Ok(f(
+                        node,
+                        &mut neighbours.map(|(w, v)| (w, v.as_ref().ok().unwrap())),
+                    )),
+                }
+            };
+            values[node.index()] = Some(value);
+        }
+        Folded {
+            values: values.into_iter().map(Option::unwrap).collect(),
+            cycles,
+        }
+    }
+
+    // For every node, how many nodes can reach it, counted as `reaching`
+    // would. Works on the strongly connected components: bit j of a
+    // component's mask says whether the j-th of up to 64 source components
+    // reaches it, and masks flow along the edges in topological order, so
+    // each pass answers 64 sources at once. The sources of a pass all have
+    // the same size, so each set bit stands for that many nodes.
+    pub fn count_reaching(&self) -> Vec<usize> {
+        let components = self.strongly_connected_components();
+        let mut component_of = vec![0; self.len()];
+        for (i, component) in components.iter().enumerate() {
+            for node in component {
+                component_of[node.index()] = i;
+            }
+        }
+        let mut by_size: Vec<usize> = (0..components.len()).collect();
+        by_size.sort_unstable_by_key(|&i| (components[i].len(), i));
+        let mut counts = vec![0; components.len()];
+        let mut masks = vec![0u64; components.len()];
+        let mut start = 0;
+        while start < by_size.len() {
+            let size = components[by_size[start]].len();
+            let end = (start..by_size.len())
+                .take(64)
+                .take_while(|&i| components[by_size[i]].len() == size)
+                .last()
+                .unwrap()
+                + 1;
+            let sources = &by_size[start..end];
+            for (bit, &source) in sources.iter().enumerate() {
+                masks[source] |= 1 << bit;
+            }
+            // Components are in reverse topological order, so everything
+            // that leads to a component comes before it here.
+            let first = *sources.last().unwrap();
+            for i in (0..=first).rev() {
+                let mask = masks[i];
+                if mask == 0 {
+                    continue;
+                }
+                masks[i] = 0;
+                counts[i] += size * mask.count_ones() as usize;
+                for node in &components[i] {
+                    for (to, _) in self.edges(*node, Direction::Outgoing) {
+                        let to = component_of[to.index()];
+                        if to != i {
+                            masks[to] |= mask;
+                        }
+                    }
+                }
+            }
+            start = end;
+        }
+        let mut reaching = vec![0; self.len()];
+        for (i, component) in components.iter().enumerate() {
+            let node = component[0];
+            let on_cycle = component.len() > 1
+                || self
+                    .edges(node, Direction::Outgoing)
+                    .iter()
+                    .any(|(n, _)| *n == node);
+            for node in component {
+                reaching[node.index()] = counts[i] - usize::from(!on_cycle);
+            }
+        }
+        reaching
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Cycle, Direction, Graph, GraphBuilder, NodeId};
+
+    fn graph(edges: &[(&str, &str)]) -> Graph<String, u32> {
+        let mut graph = GraphBuilder::new();
+        for &(from, to) in edges {
+            let from = graph.intern(from);
+            let to = graph.intern(to);
+            graph.add_edge(from, to, 1);
+        }
+        graph.build()
+    }
+
+    fn names(graph: &Graph<String, u32>, nodes: &[NodeId]) -> Vec<String> {
+        nodes.iter().map(|&n| graph.key(n).clone()).collect()
+    }
+
+    // Checks that every edge goes forwards in `order`.
+    fn assert_topological(graph: &Graph<String, u32>, order: &[NodeId]) {
+        let mut position = vec![0; graph.len()];
+        for (i, node) in order.iter().enumerate() {
+            position[node.index()] = i;
+        }
+        for node in graph.nodes() {
+            for (to, _) in graph.edges(node, Direction::Outgoing) {
+                assert!(position[node.index()] < position[to.index()]);
+            }
+        }
+    }
+
+    #[test]
+    fn topological_sort_acyclic() {
+        let graph = graph(&[("a", "b"), ("a", "c"), ("c", "b"), ("b", "d"), ("e", "c")]);
+        let order = graph.topological_sort().unwrap();
+        assert_eq!(order.len(), graph.len());
+        assert_topological(&graph, &order);
+        assert_eq!(graph.find_cycle(), None);
+    }
+
+    #[test]
+    fn topological_sort_cycle() {
+        let graph = graph(&[("a", "b"), ("b", "c"), ("c", "d"), ("d", "b")]);
+        let Cycle(cycle) = graph.topological_sort().unwrap_err();
+        assert_eq!(names(&graph, &cycle), vec!["b", "c", "d", "b"]);
+        assert_eq!(graph.find_cycle(), Some(Cycle(cycle)));
+    }
+
+    #[test]
+    fn self_loop() {
+        let graph = graph(&[("a", "a")]);
+        let Cycle(cycle) = graph.find_cycle().unwrap();
+        assert_eq!(names(&graph, &cycle), vec!["a", "a"]);
+    }
+
+    #[test]
+    fn strongly_connected_components() {
+        let graph = graph(&[
+            ("a", "b"),
+            ("b", "c"),
+            ("c", "a"),
+            ("c", "d"),
+            ("d", "e"),
+            ("e", "d"),
+            ("e", "f"),
+            ("g", "g"),
+        ]);
+        let components = graph.strongly_connected_components();
+        let mut sorted: Vec<Vec<String>> = components
+            .iter()
+            .map(|c| {
+                let mut c = names(&graph, c);
+                c.sort();
+                c
+            })
+            .collect();
+        sorted.sort();
+        assert_eq!(
+            sorted,
+            vec![vec!["a", "b", "c"], vec!["d", "e"], vec!["f"], vec!["g"]]
+        );
+        // No edge leads to a component listed later.
+        let mut component_of = vec![0; graph.len()];
+        for (i, component) in components.iter().enumerate() {
+            for node in component {
+                component_of[node.index()] = i;
+            }
+        }
+        for node in graph.nodes() {
+            for (to, _) in graph.edges(node, Direction::Outgoing) {
+                assert!(component_of[to.index()] <= component_of[node.index()]);
+            }
+        }
+    }
+
+    #[test]
+    fn fold_dag_counts_paths() {
+        let graph = graph(&[("a", "b"), ("a", "c"), ("b", "d"), ("c", "d")]);
+        let a = graph.node("a").unwrap();
+        let paths = graph
+            .fold_dag(&[a], Direction::Outgoing, |_, next| {
+                next.map(|(_, paths)| *paths).sum::<u32>().max(1)
+            })
+            .unwrap();
+        assert_eq!(paths.get(a), Some(&2));
+        assert!(graph
+            .fold_dag(&[graph.node("d").unwrap()], Direction::Incoming, |_, _| ())
+            .is_ok());
+    }
+
+    #[test]
+    fn count_reaching_matches_reaching() {
+        let mut edges = vec![
+            ("a", "b"),
+            ("b", "c"),
+            ("c", "a"),
+            ("c", "d"),
+            ("d", "e"),
+            ("e", "d"),
+            ("e", "f"),
+            ("g", "g"),
+            ("g", "f"),
+            ("h", "f"),
+        ];
+        // Enough single nodes to need more than one pass.
+        let names: Vec<String> = (0..150).map(|i| format!("n{}", i)).collect();
+        for (i, name) in names.iter().enumerate().skip(1) {
+            edges.push((&names[i / 2], name));
+            edges.push((name, "h"));
+        }
+        let graph = graph(&edges);
+        let counts = graph.count_reaching();
+        for node in graph.nodes() {
+            assert_eq!(counts[node.index()], graph.reaching(node).len());
+        }
+        assert_eq!(counts[graph.node("f").unwrap().index()], 157);
+    }
+
+    #[test]
+    fn fold_all_spoils_only_what_reaches_a_cycle() {
+        let graph = graph(&[("a", "b"), ("b", "c"), ("c", "b"), ("d", "e")]);
+        let depth = graph.fold_all(|_, next| next.map(|(_, d)| d + 1).max().unwrap_or(0));
+        let get = |name: &str| {
+            let node = graph.node(name).unwrap();
+            depth
+                .get(node)
+                .copied()
+                .map_err(|Cycle(cycle)| names(&graph, cycle))
+        };
+        assert_eq!(get("d"), Ok(1));
+        assert_eq!(get("e"), Ok(0));
+        for name in ["a", "b", "c"].iter() {
+            let cycle = get(name).unwrap_err();
+            assert_eq!(cycle.len(), 3);
+            assert_eq!(cycle[0], cycle[2]);
+        }
+    }
+}
@@ -0,0 +1,329 @@
+use std::borrow::Borrow;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+
+mod dag;
+
+pub use dag::{Cycle, Folded, Memo};
+
+// Dense id handed out when a node key is interned. Ids are assigned in
+// insertion order starting at 0, so they can index plain vectors.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(u32);
+
+impl NodeId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Outgoing,
+    Incoming,
+}
+
+// The edges of every node packed into one vector, sorted by node: the edges
+// of node i are edges[offsets[i]..offsets[i + 1]]. This needs two
+// allocations however many nodes there are.
+struct Adjacency<W> {
+    offsets: Vec<u32>,
+    edges: Vec<(NodeId, W)>,
+}
+
+impl<W> Adjacency<W> {
+    // Groups `edges`, given as (node, edge) pairs, by node. Edges of the same
+    // node keep their order.
+    fn build(nodes: usize, mut edges: Vec<(NodeId, (NodeId, W))>) -> Adjacency<W> {
+        assert!(edges.len() < u32::MAX as usize, "Too many edges");
+        edges.sort_by_key(|(node, _)| *node);
+        let mut offsets = vec![0u32; nodes + 1];
+        for (node, _) in &edges {
+            offsets[node.index() + 1] += 1;
+        }
+        for i in 0..nodes {
+            offsets[i + 1] += offsets[i];
+        }
+        Adjacency {
+            offsets,
+            edges: edges.into_iter().map(|(_, edge)| edge).collect(),
+        }
+    }
+
+    fn get(&self, node: NodeId) -> &[(NodeId, W)] {
+        let i = node.index();
+        &self.edges[self.offsets[i] as usize..self.offsets[i + 1] as usize]
+    }
+}
+
+// Collects nodes and edges, then turns them into a `Graph`.
+pub struct GraphBuilder<K, W> {
+    ids: HashMap<K, NodeId>,
+    edges: Vec<(NodeId, NodeId, W)>,
+}
+
+impl<K, W> Default for GraphBuilder<K, W> {
+    fn default() -> Self {
+        GraphBuilder {
+            ids: HashMap::new(),
+            edges: Vec::new(),
+        }
+    }
+}
+
+impl<K: Hash + Ord, W: Clone> GraphBuilder<K, W> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    // Returns the id for `key`, adding a new node if it wasn't seen before.
+    pub fn intern<Q>(&mut self, key: &Q) -> NodeId
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+    {
+        if let Some(id) = self.ids.get(key) {
+            return *id;
+        }
+        assert!(self.ids.len() < u32::MAX as usize, "Too many nodes");
+        let id = NodeId(self.ids.len() as u32);
+        self.ids.insert(key.to_owned(), id);
+        id
+    }
+
+    pub fn add_edge(&mut self, from: NodeId, to: NodeId, weight: W) {
+        self.edges.push((from, to, weight));
+    }
+
+    pub fn build(self) -> Graph<K, W> {
+        let nodes = self.ids.len();
+        let mut entries: Vec<(K, NodeId)> = self.ids.into_iter().collect();
+        entries.sort_unstable_by_key(|(_, id)| *id);
+        let keys: Vec<K> = entries.into_iter().map(|(key, _)| key).collect();
+        let mut by_key: Vec<NodeId> = (0..nodes as u32).map(NodeId).collect();
+        by_key.sort_unstable_by(|a, b| keys[a.index()].cmp(&keys[b.index()]));
+        let incoming = self
+            .edges
+            .iter()
+            .map(|(from, to, weight)| (*to, (*from, weight.clone())))
+            .collect();
+        let outgoing = self
+            .edges
+            .into_iter()
+            .map(|(from, to, weight)| (from, (to, weight)))
+            .collect();
+        Graph {
+            keys,
+            by_key,
+            outgoing: Adjacency::build(nodes, outgoing),
+            incoming: Adjacency::build(nodes, incoming),
+        }
+    }
+}
+
+// A weighted directed graph, built with a `GraphBuilder`. Every edge is
+// stored in both directions so traversals can walk the graph backwards as
+// cheaply as forwards. Each key is stored once; finding a node by key is a
+// binary search over the ids sorted by key.
+pub struct Graph<K, W> {
+    keys: Vec<K>,
+    by_key: Vec<NodeId>,
+    outgoing: Adjacency<W>,
+    incoming: Adjacency<W>,
+}
+
+impl<K: Ord, W> Graph<K, W> {
+    pub fn node<Q>(&self, key: &Q) -> Option<NodeId>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let i = self
+            .by_key
+            .binary_search_by(|id| self.keys[id.index()].borrow().cmp(key))
+            .ok()?;
+        Some(self.by_key[i])
+    }
+}
+
+impl<K, W> Graph<K, W> {
+    pub fn key(&self, id: NodeId) -> &K {
+        &self.keys[id.index()]
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = NodeId> {
+        (0..self.keys.len() as u32).map(NodeId)
+    }
+
+    pub fn edges(&self, from: NodeId, direction: Direction) -> &[(NodeId, W)] {
+        match direction {
+            Direction::Outgoing => self.outgoing.get(from),
+            Direction::Incoming => self.incoming.get(from),
+        }
+    }
+
+    // Lists every node reachable from `start` (including `start` itself) so
+    // that each node comes after all the nodes reachable from it, unless they
+    // are part of a cycle.
+    pub fn dfs_post_order(&self, start: NodeId, direction: Direction) -> Vec<NodeId> {
+        let mut order: Vec<NodeId> = Vec::new();
+        let mut visited = vec![false; self.len()];
+        let mut stack: Vec<(NodeId, usize)> = vec![(start, 0)];
+        visited[start.index()] = true;
+        while let Some((node, next_edge)) = stack.last_mut() {
+            let node = *node;
+            match self.edges(node, direction).get(*next_edge) {
+                Some((neighbour, _)) => {
+                    *next_edge += 1;
+                    if !visited[neighbour.index()] {
+                        visited[neighbour.index()] = true;
+                        stack.push((*neighbour, 0));
+                    }
+                }
+                None => {
+                    stack.pop();
+                    order.push(node);
+                }
+            }
+        }
+        order
+    }
+
+    // Lists every node reachable from `start` together with its distance in
+    // edges, closest nodes first.
+    pub fn bfs(&self, start: NodeId, direction: Direction) -> Vec<(NodeId, usize)> {
+        let mut order: Vec<(NodeId, usize)> = Vec::new();
+        let mut visited = vec![false; self.len()];
+        let mut queue: VecDeque<(NodeId, usize)> = VecDeque::new();
+        visited[start.index()] = true;
+        queue.push_back((start, 0));
+        while let Some((node, depth)) = queue.pop_front() {
+            order.push((node, depth));
+            for (neighbour, _) in self.edges(node, direction) {
+                if !visited[neighbour.index()] {
+                    visited[neighbour.index()] = true;
+                    queue.push_back((*neighbour, depth + 1));
+                }
+            }
+        }
+        order
+    }
+
+    // All nodes that can reach `target`, not counting `target` itself unless
+    // it sits on a cycle, which it does when one of the nodes it leads to can
+    // reach it.
+    pub fn reaching(&self, target: NodeId) -> Vec<NodeId> {
+        let mut reaching: Vec<NodeId> = Vec::new();
+        let mut visited = vec![false; self.len()];
+        let mut queue: VecDeque<NodeId> = VecDeque::new();
+        visited[target.index()] = true;
+        queue.push_back(target);
+        while let Some(node) = queue.pop_front() {
+            for (neighbour, _) in self.edges(node, Direction::Incoming) {
+                if !visited[neighbour.index()] {
+                    visited[neighbour.index()] = true;
+                    reaching.push(*neighbour);
+                    queue.push_back(*neighbour);
+                }
+            }
+        }
+        let on_cycle = self
+            .edges(target, Direction::Outgoing)
+            .iter()
+            .any(|(n, _)| visited[n.index()]);
+        if on_cycle {
+            reaching.push(target);
+        }
+        reaching
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(edges: &[(u32, u32)]) -> Graph<u32, ()> {
+        let mut graph = GraphBuilder::new();
+        for &(from, to) in edges {
+            let from = graph.intern(&from);
+            let to = graph.intern(&to);
+            graph.add_edge(from, to, ());
+        }
+        graph.build()
+    }
+
+    fn keys(graph: &Graph<u32, ()>, nodes: impl IntoIterator<Item = NodeId>) -> Vec<u32> {
+        nodes.into_iter().map(|n| *graph.key(n)).collect()
+    }
+
+    #[test]
+    fn interning() {
+        let graph = graph(&[(5, 3), (3, 9), (5, 9)]);
+        assert_eq!(graph.len(), 3);
+        assert_eq!(keys(&graph, graph.nodes()), vec![5, 3, 9]);
+        assert_eq!(graph.node(&9), Some(NodeId(2)));
+        assert_eq!(graph.node(&4), None);
+        let five = graph.node(&5).unwrap();
+        assert_eq!(
+            keys(
+                &graph,
+                graph.edges(five, Direction::Outgoing).iter().map(|e| e.0)
+            ),
+            vec![3, 9]
+        );
+        let nine = graph.node(&9).unwrap();
+        assert_eq!(
+            keys(
+                &graph,
+                graph.edges(nine, Direction::Incoming).iter().map(|e| e.0)
+            ),
+            vec![3, 5]
+        );
+    }
+
+    #[test]
+    fn dfs_post_order() {
+        let graph = graph(&[(1, 2), (2, 3), (1, 4), (4, 3), (3, 1)]);
+        let one = graph.node(&1).unwrap();
+        assert_eq!(
+            keys(&graph, graph.dfs_post_order(one, Direction::Outgoing)),
+            vec![3, 2, 4, 1]
+        );
+        let three = graph.node(&3).unwrap();
+        assert_eq!(
+            keys(&graph, graph.dfs_post_order(three, Direction::Incoming)),
+            vec![1, 2, 4, 3]
+        );
+    }
+
+    #[test]
+    fn bfs() {
+        let graph = graph(&[(1, 2), (2, 3), (1, 3), (3, 4)]);
+        let one = graph.node(&1).unwrap();
+        let found: Vec<(u32, usize)> = graph
+            .bfs(one, Direction::Outgoing)
+            .into_iter()
+            .map(|(n, depth)| (*graph.key(n), depth))
+            .collect();
+        assert_eq!(found, vec![(1, 0), (2, 1), (3, 1), (4, 2)]);
+    }
+
+    #[test]
+    fn reaching() {
+        let graph = graph(&[(1, 2), (2, 3), (4, 3), (5, 5), (6, 5)]);
+        let mut reaching = keys(&graph, graph.reaching(graph.node(&3).unwrap()));
+        reaching.sort_unstable();
+        assert_eq!(reaching, vec![1, 2, 4]);
+        let mut reaching = keys(&graph, graph.reaching(graph.node(&5).unwrap()));
+        reaching.sort_unstable();
+        assert_eq!(reaching, vec![5, 6]);
+    }
+}
@@ -0,0 +1,162 @@
+use crate::{mod_inverse, sub_mod, Int};
+use num_bigint::BigUint;
+
+// x == value mod modulus
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Congruence<T> {
+    pub value: T,
+    pub modulus: T,
+}
+
+// Solves a system of congruences by merging them two at a time. The moduli
+// don't need to be coprime; returns None if the system has no solution.
+//
+// The combined modulus is the lcm of all moduli and must fit in T, so use
+// BigUint when that can get large.
+//
+// See: https://cp-algorithms.com/algebra/chinese-remainder-theorem.html
+pub fn crt<T: Int>(congruences: &[Congruence<T>]) -> Option<Congruence<T>> {
+    let mut result = Congruence {
+        value: T::zero(),
+        modulus: T::one(),
+    };
+    for c in congruences {
+        let g = result.modulus.gcd(&c.modulus);
+        let value = c.value.mod_floor(&c.modulus);
+        let diff = sub_mod(value, result.value.mod_floor(&c.modulus), &c.modulus);
+        if !(diff.clone() % g.clone()).is_zero() {
+            return None;
+        }
+        // Find k with result.value + result.modulus * k == value mod c.modulus.
+        let reduced = c.modulus.clone() / g.clone();
+        let inv = mod_inverse(result.modulus.clone() / g.clone(), reduced.clone())?;
+        let k = (diff / g).mod_floor(&reduced).mul_mod(&inv, &reduced);
+        let modulus = result.modulus.lcm(&c.modulus);
+        result = Congruence {
+            value: result.value + result.modulus * k,
+            modulus,
+        };
+    }
+    Some(result)
+}
+
+// Finds a solution to the chinese remainder theorem using garner's
+// algorithm. The moduli must be pairwise coprime; returns None otherwise.
+//
+// The mixed radix digits all fit in an i64, only the final sum needs a big
+// integer.
+//
+// See: https://cp-algorithms.com/algebra/chinese-remainder-theorem.html
+pub fn garner(congruences: &[Congruence<i64>]) -> Option<BigUint> {
+    let mut x: Vec<i64> = Vec::with_capacity(congruences.len());
+    for (i, c) in congruences.iter().enumerate() {
+        let mut x_i = c.value.rem_euclid(c.modulus);
+        for (prev, x_j) in congruences[..i].iter().zip(x.iter()) {
+            let r_j_i = mod_inverse(prev.modulus, c.modulus)?;
+            x_i = r_j_i.mul_mod(&(x_i - x_j).rem_euclid(c.modulus), &c.modulus);
+        }
+        x.push(x_i);
+    }
+
+    let mut result = BigUint::default();
+    let mut radix = BigUint::from(1u8);
+    for (c, x_i) in congruences.iter().zip(x) {
+        result += &radix * x_i as u64;
+        radix *= c.modulus as u64;
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn congruences(pairs: &[(i64, i64)]) -> Vec<Congruence<i64>> {
+        pairs
+            .iter()
+            .map(|&(value, modulus)| Congruence { value, modulus })
+            .collect()
+    }
+
+    #[test]
+    fn crt_coprime() {
+        let solution = crt(&congruences(&[(2, 3), (3, 5), (2, 7)]));
+        assert_eq!(
+            solution,
+            Some(Congruence {
+                value: 23,
+                modulus: 105
+            })
+        );
+    }
+
+    #[test]
+    fn crt_not_coprime() {
+        let solution = crt(&congruences(&[(2, 4), (4, 6)]));
+        assert_eq!(
+            solution,
+            Some(Congruence {
+                value: 10,
+                modulus: 12
+            })
+        );
+        let solution = crt(&congruences(&[(3, 6), (5, 10), (0, 15)]));
+        assert_eq!(
+            solution,
+            Some(Congruence {
+                value: 15,
+                modulus: 30
+            })
+        );
+    }
+
+    #[test]
+    fn crt_no_solution() {
+        assert_eq!(crt(&congruences(&[(1, 4), (2, 6)])), None);
+    }
+
+    #[test]
+    fn crt_negative_values() {
+        let solution = crt(&congruences(&[(-1, 4), (-2, 9)]));
+        assert_eq!(
+            solution,
+            Some(Congruence {
+                value: 7,
+                modulus: 36
+            })
+        );
+    }
+
+    #[test]
+    fn crt_big() {
+        let modulus = |p: u64| BigUint::from(p);
+        let solution = crt(&[
+            Congruence {
+                value: modulus(1),
+                modulus: modulus(1_000_000_007),
+            },
+            Congruence {
+                value: modulus(2),
+                modulus: modulus(998_244_353),
+            },
+        ])
+        .unwrap();
+        assert_eq!(
+            solution.modulus,
+            modulus(1_000_000_007) * modulus(998_244_353)
+        );
+        assert_eq!(&solution.value % modulus(1_000_000_007), modulus(1));
+        assert_eq!(&solution.value % modulus(998_244_353), modulus(2));
+    }
+
+    #[test]
+    fn garner_matches_crt() {
+        let system = congruences(&[(2, 3), (3, 5), (2, 7)]);
+        assert_eq!(garner(&system), Some(BigUint::from(23u8)));
+    }
+
+    #[test]
+    fn garner_not_coprime() {
+        assert_eq!(garner(&congruences(&[(2, 4), (4, 6)])), None);
+    }
+}
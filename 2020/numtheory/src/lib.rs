@@ -0,0 +1,288 @@
+use num_bigint::{BigInt, BigUint};
+use num_integer::{Integer, Roots};
+use num_traits::{One, Signed, Zero};
+use std::collections::HashMap;
+use std::hash::Hash;
+
+mod crt;
+mod primes;
+
+pub use crt::{crt, garner, Congruence};
+pub use primes::{factorize, is_prime};
+
+// The integer types the helpers in this crate work with. Everything is written
+// against this trait so the same code serves i64, i128 and BigUint.
+pub trait Int: Integer + Clone + Hash + Roots + From<u8> {
+    // Type used for the (possibly negative) coefficients of the extended
+    // euclidean algorithm.
+    type Signed: Integer + Signed + Clone + From<Self>;
+
+    // Computes self*other mod modulus without overflowing for any modulus that
+    // fits in Self. Both operands must already be reduced.
+    fn mul_mod(&self, other: &Self, modulus: &Self) -> Self;
+}
+
+impl Int for i64 {
+    type Signed = i64;
+
+    fn mul_mod(&self, other: &i64, modulus: &i64) -> i64 {
+        (*self as i128 * *other as i128).rem_euclid(*modulus as i128) as i64
+    }
+}
+
+impl Int for i128 {
+    type Signed = i128;
+
+    fn mul_mod(&self, other: &i128, modulus: &i128) -> i128 {
+        if let Some(product) = self.checked_mul(*other) {
+            return product.rem_euclid(*modulus);
+        }
+        // Too big for a direct multiplication, fall back to double-and-add
+        // where every intermediate value stays below the modulus.
+        let add_mod = |a: i128, b: i128| if a >= modulus - b { a - (modulus - b) } else { a + b };
+        let mut result = 0;
+        let mut a = self.rem_euclid(*modulus);
+        let mut b = other.rem_euclid(*modulus);
+        while b > 0 {
+            if b & 1 == 1 {
+                result = add_mod(result, a);
+            }
+            a = add_mod(a, a);
+            b >>= 1;
+        }
+        result
+    }
+}
+
+impl Int for BigUint {
+    type Signed = BigInt;
+
+    fn mul_mod(&self, other: &BigUint, modulus: &BigUint) -> BigUint {
+        (self * other) % modulus
+    }
+}
+
+pub fn gcd<T: Int>(a: T, b: T) -> T {
+    a.gcd(&b)
+}
+
+pub fn lcm<T: Int>(a: T, b: T) -> T {
+    a.lcm(&b)
+}
+
+// Computes gcd(a, b) and also coefficients x, y for
+// which a*x + b*y == d.
+//
+// See: https://cp-algorithms.com/algebra/extended-euclid-algorithm.html
+pub fn extended_euclid<T: Int>(a: T, b: T) -> (T::Signed, T::Signed, T::Signed) {
+    let (mut old_r, mut r) = (T::Signed::from(a), T::Signed::from(b));
+    let (mut old_x, mut x) = (T::Signed::one(), T::Signed::zero());
+    let (mut old_y, mut y) = (T::Signed::zero(), T::Signed::one());
+    while !r.is_zero() {
+        let q = old_r.clone() / r.clone();
+        let next_r = old_r - q.clone() * r.clone();
+        old_r = std::mem::replace(&mut r, next_r);
+        let next_x = old_x - q.clone() * x.clone();
+        old_x = std::mem::replace(&mut x, next_x);
+        let next_y = old_y - q * y.clone();
+        old_y = std::mem::replace(&mut y, next_y);
+    }
+    (old_r, old_x, old_y)
+}
+
+// Computes a - b mod modulus for already reduced operands, without ever going
+// negative so it also works for unsigned types.
+fn sub_mod<T: Int>(a: T, b: T, modulus: &T) -> T {
+    if a >= b {
+        a - b
+    } else {
+        modulus.clone() - (b - a)
+    }
+}
+
+// Finds x such that a*x == 1 mod modulus. Only exists if a and modulus are
+// coprime.
+//
+// This is the extended euclidean algorithm again, but the coefficient of `a`
+// is tracked modulo `modulus` so it never needs a signed type.
+pub fn mod_inverse<T: Int>(a: T, modulus: T) -> Option<T> {
+    let (mut old_r, mut r) = (a.mod_floor(&modulus), modulus.clone());
+    let (mut old_x, mut x) = (T::one().mod_floor(&modulus), T::zero());
+    while !r.is_zero() {
+        let (q, rem) = old_r.div_rem(&r);
+        old_r = std::mem::replace(&mut r, rem);
+        let qx = q.mod_floor(&modulus).mul_mod(&x, &modulus);
+        let next_x = sub_mod(old_x, qx, &modulus);
+        old_x = std::mem::replace(&mut x, next_x);
+    }
+    if !old_r.is_one() {
+        return None;
+    }
+    Some(old_x)
+}
+
+// Computes base^exp mod modulus by repeated squaring. The exponent must not be
+// negative.
+pub fn mod_pow<T: Int>(base: T, mut exp: T, modulus: T) -> T {
+    assert!(exp >= T::zero(), "mod_pow needs a non-negative exponent");
+    let two = T::from(2);
+    let mut result = T::one().mod_floor(&modulus);
+    let mut base = base.mod_floor(&modulus);
+    while !exp.is_zero() {
+        if exp.is_odd() {
+            result = result.mul_mod(&base, &modulus);
+        }
+        base = base.mul_mod(&base, &modulus);
+        exp = exp / two.clone();
+    }
+    result
+}
+
+// Finds the smallest x >= 0 for which base^x == target mod modulus, using the
+// baby-step giant-step algorithm. Needs base and modulus to be coprime.
+//
+// See: https://cp-algorithms.com/algebra/discrete-log.html
+pub fn discrete_log<T: Int>(base: T, target: T, modulus: T) -> Option<T> {
+    let base = base.mod_floor(&modulus);
+    let step = modulus.sqrt() + T::one();
+
+    // Baby steps: base^j for j in 0..step, keeping the smallest j for each
+    // value so the final answer is the smallest exponent.
+    let mut baby: HashMap<T, T> = HashMap::new();
+    let mut value = T::one().mod_floor(&modulus);
+    let mut j = T::zero();
+    while j < step {
+        let next = value.mul_mod(&base, &modulus);
+        baby.entry(value).or_insert_with(|| j.clone());
+        value = next;
+        j = j + T::one();
+    }
+
+    // Giant steps: target * base^(-step*i) for i in 0..step.
+    let giant = mod_pow(mod_inverse(base, modulus.clone())?, step.clone(), modulus.clone());
+    let mut gamma = target.mod_floor(&modulus);
+    let mut i = T::zero();
+    while i < step {
+        if let Some(j) = baby.get(&gamma) {
+            return Some(i * step + j.clone());
+        }
+        gamma = gamma.mul_mod(&giant, &modulus);
+        i = i + T::one();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Debug;
+
+    // Checks gcd, lcm and the extended euclid coefficients of `a` and `b`.
+    fn check_euclid<T>(a: T, b: T, expected_gcd: T, expected_lcm: T)
+    where
+        T: Int + Debug,
+        T::Signed: Debug,
+    {
+        assert_eq!(gcd(a.clone(), b.clone()), expected_gcd);
+        assert_eq!(lcm(a.clone(), b.clone()), expected_lcm);
+        let (d, x, y) = extended_euclid(a.clone(), b.clone());
+        assert_eq!(d, T::Signed::from(expected_gcd));
+        assert_eq!(T::Signed::from(a) * x + T::Signed::from(b) * y, d);
+    }
+
+    #[test]
+    fn euclid_i64() {
+        check_euclid(240i64, 46, 2, 5520);
+        check_euclid(17i64, 5, 1, 85);
+        check_euclid(0i64, 7, 7, 0);
+        check_euclid(12i64, 12, 12, 12);
+    }
+
+    #[test]
+    fn euclid_i128() {
+        let a = 3i128 << 100;
+        let b = 5i128 << 90;
+        check_euclid(a, b, 1 << 90, 15 << 100);
+        let prime = (1i128 << 61) - 1;
+        let b = 1_000_000_000_000_000_000;
+        check_euclid(prime, b, 1, prime * b);
+    }
+
+    #[test]
+    fn euclid_big() {
+        let a = BigUint::from(3u8) << 200;
+        let b = BigUint::from(5u8) << 190;
+        check_euclid(a, b, BigUint::from(1u8) << 190, BigUint::from(15u8) << 200);
+        check_euclid(
+            BigUint::from(20201227u32),
+            BigUint::from(7u8),
+            BigUint::from(1u8),
+            BigUint::from(141408589u32),
+        );
+    }
+
+    // The day 25 sample: the card's public key comes from 8 loops, the
+    // door's from 11, and both give the same encryption key.
+    #[test]
+    fn discrete_log_day25_sample() {
+        let modulus = 20201227i64;
+        assert_eq!(discrete_log(7, 5764801, modulus), Some(8));
+        assert_eq!(discrete_log(7, 17807724, modulus), Some(11));
+        assert_eq!(mod_pow(17807724, 8, modulus), 14897079);
+        assert_eq!(mod_pow(5764801, 11, modulus), 14897079);
+        let big = |n: u32| BigUint::from(n);
+        assert_eq!(
+            discrete_log(big(7), big(17807724), big(20201227)),
+            Some(big(11))
+        );
+    }
+
+    #[test]
+    fn discrete_log_without_solution() {
+        // Powers of 2 mod 7 are 1, 2 and 4.
+        assert_eq!(discrete_log(2i64, 3, 7), None);
+        // 2 has no inverse mod 8.
+        assert_eq!(discrete_log(2i64, 3, 8), None);
+        assert_eq!(discrete_log(3i64, 1, 7), Some(0));
+    }
+
+    #[test]
+    fn mod_inverse_coprime() {
+        assert_eq!(mod_inverse(3i64, 11), Some(4));
+        assert_eq!(mod_inverse(-3i64, 11), Some(7));
+        assert_eq!(mod_inverse(6i64, 9), None);
+        assert_eq!(mod_inverse(0i64, 5), None);
+        assert_eq!(
+            mod_inverse(BigUint::from(3u8), BigUint::from(11u8)),
+            Some(BigUint::from(4u8))
+        );
+        assert_eq!(mod_inverse(BigUint::from(6u8), BigUint::from(9u8)), None);
+    }
+
+    #[test]
+    fn i128_beyond_direct_products() {
+        // 2^127 - 1 is prime, and products of residues overflow an i128.
+        let modulus = i128::MAX;
+        assert_eq!((modulus - 1).mul_mod(&(modulus - 1), &modulus), 1);
+        assert_eq!((1i128 << 100).mul_mod(&(1 << 100), &modulus), 1 << 73);
+        assert_eq!(mod_pow(3, modulus - 1, modulus), 1);
+        assert_eq!(mod_pow(2, 127, modulus), 1);
+        let inverse = mod_inverse(1i128 << 100, modulus).unwrap();
+        assert_eq!(inverse.mul_mod(&(1 << 100), &modulus), 1);
+    }
+
+    #[test]
+    fn mod_pow_edges() {
+        assert_eq!(mod_pow(5i64, 0, 13), 1);
+        assert_eq!(mod_pow(5i64, 0, 1), 0);
+        assert_eq!(mod_pow(-2i64, 3, 13), 5);
+        assert_eq!(
+            mod_pow(
+                BigUint::from(2u8),
+                BigUint::from(100u8),
+                BigUint::from(1u8) << 64
+            ),
+            BigUint::default()
+        );
+    }
+}
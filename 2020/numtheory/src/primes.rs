@@ -0,0 +1,168 @@
+use crate::{mod_pow, Int};
+
+// Enough Miller-Rabin bases to make the test deterministic for anything below
+// 3.3 * 10^24, which covers every i64 and a good part of i128. Above that a
+// number passing all of them is a strong probable prime.
+const WITNESSES: [u8; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+// See: https://cp-algorithms.com/algebra/primality_tests.html
+pub fn is_prime<T: Int>(n: &T) -> bool {
+    if *n < T::from(2) {
+        return false;
+    }
+    for &p in WITNESSES.iter() {
+        let p = T::from(p);
+        if *n == p {
+            return true;
+        }
+        if n.is_multiple_of(&p) {
+            return false;
+        }
+    }
+
+    let n_minus_one = n.clone() - T::one();
+    let mut d = n_minus_one.clone();
+    let mut s = 0;
+    while d.is_even() {
+        d = d / T::from(2);
+        s += 1;
+    }
+
+    'witness: for &a in WITNESSES.iter() {
+        let mut x = mod_pow(T::from(a), d.clone(), n.clone());
+        if x.is_one() || x == n_minus_one {
+            continue;
+        }
+        for _ in 1..s {
+            x = x.mul_mod(&x, n);
+            if x == n_minus_one {
+                continue 'witness;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+// Finds a non-trivial divisor of n, which must be composite and have no small
+// factors.
+//
+// See: https://cp-algorithms.com/algebra/factorization.html#pollards-rho-algorithm
+fn pollard_rho<T: Int>(n: &T) -> T {
+    let mut c = T::one();
+    loop {
+        let f = |x: &T| (x.mul_mod(x, n) + c.clone()).mod_floor(n);
+        let mut x = T::from(2);
+        let mut y = x.clone();
+        let mut d = T::one();
+        while d.is_one() {
+            x = f(&x);
+            y = f(&f(&y));
+            let diff = if x > y { x.clone() - y.clone() } else { y.clone() - x.clone() };
+            d = diff.gcd(n);
+        }
+        if d != *n {
+            return d;
+        }
+        c = c + T::one();
+    }
+}
+
+fn collect_prime_factors<T: Int>(n: T, factors: &mut Vec<T>) {
+    if n.is_one() {
+        return;
+    }
+    if is_prime(&n) {
+        factors.push(n);
+        return;
+    }
+    let d = pollard_rho(&n);
+    let rest = n / d.clone();
+    collect_prime_factors(d, factors);
+    collect_prime_factors(rest, factors);
+}
+
+// Splits n into its prime factors, returned as (prime, exponent) pairs sorted
+// by prime. Small primes are divided out directly, the rest is left to
+// pollard's rho.
+pub fn factorize<T: Int>(mut n: T) -> Vec<(T, u32)> {
+    assert!(n > T::zero(), "factorize needs a positive number");
+    let mut factors: Vec<T> = Vec::new();
+    for &p in WITNESSES.iter() {
+        let p = T::from(p);
+        while n.is_multiple_of(&p) {
+            n = n / p.clone();
+            factors.push(p.clone());
+        }
+    }
+    collect_prime_factors(n, &mut factors);
+    factors.sort();
+
+    let mut result: Vec<(T, u32)> = Vec::new();
+    for p in factors {
+        match result.last_mut() {
+            Some((last, count)) if *last == p => *count += 1,
+            _ => result.push((p, 1)),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::BigUint;
+
+    #[test]
+    fn small_numbers() {
+        let primes: Vec<i64> = (0..50).filter(is_prime).collect();
+        assert_eq!(
+            primes,
+            vec![2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47]
+        );
+    }
+
+    #[test]
+    fn carmichael_numbers() {
+        for &n in [561i64, 1105, 41041, 825265, 321197185].iter() {
+            assert!(!is_prime(&n), "{} is a Carmichael number", n);
+        }
+        // A strong pseudoprime to the bases 2, 3, 5 and 7.
+        assert!(!is_prime(&3215031751i64));
+    }
+
+    #[test]
+    fn i128_primes() {
+        // 2^61 - 1 and 2^89 - 1 are Mersenne primes, 2^67 - 1 isn't.
+        assert!(is_prime(&((1i128 << 61) - 1)));
+        assert!(is_prime(&((1i128 << 89) - 1)));
+        assert!(!is_prime(&((1i128 << 67) - 1)));
+        assert!(is_prime(&i128::MAX));
+    }
+
+    #[test]
+    fn big_primes() {
+        assert!(is_prime(&BigUint::from(1_000_000_007u32)));
+        assert!(!is_prime(
+            &(BigUint::from(1_000_000_007u32) * 998_244_353u32)
+        ));
+    }
+
+    #[test]
+    fn factorize_small() {
+        assert_eq!(factorize(1i64), vec![]);
+        assert_eq!(factorize(360i64), vec![(2, 3), (3, 2), (5, 1)]);
+        assert_eq!(factorize(561i64), vec![(3, 1), (11, 1), (17, 1)]);
+    }
+
+    #[test]
+    fn factorize_large() {
+        // 2^67 - 1, which Cole factored by hand in 1903.
+        assert_eq!(
+            factorize((1i128 << 67) - 1),
+            vec![(193_707_721, 1), (761_838_257_287, 1)]
+        );
+        let n = 1_000_000_007i128 * 1_000_000_007 * 998_244_353;
+        assert_eq!(factorize(n), vec![(998_244_353, 1), (1_000_000_007, 2)]);
+    }
+}
@@ -0,0 +1,433 @@
+use std::fmt;
+use std::str::FromStr;
+
+mod records;
+
+pub use records::{Record, RecordError, RecordReader};
+
+// Why a parser didn't match: the byte span of the offending input and a
+// description of what would have been accepted there.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Failure {
+    pub start: usize,
+    pub end: usize,
+    pub expected: String,
+}
+
+// On success, the parsed value and the position right after it.
+pub type PResult<T> = Result<(T, usize), Failure>;
+
+fn fail<T>(start: usize, end: usize, expected: impl Into<String>) -> PResult<T> {
+    Err(Failure {
+        start,
+        end,
+        expected: expected.into(),
+    })
+}
+
+// Anything that can parse a value from `input` starting at byte `pos`. Plain
+// closures with the right signature are parsers, and the provided methods
+// combine parsers into bigger ones.
+pub trait Parser<'a>: Sized {
+    type Output;
+
+    fn parse_at(&self, input: &'a str, pos: usize) -> PResult<Self::Output>;
+
+    fn map<U, F>(self, f: F) -> impl Parser<'a, Output = U>
+    where
+        F: Fn(Self::Output) -> U,
+    {
+        move |input: &'a str, pos: usize| {
+            let (value, end) = self.parse_at(input, pos)?;
+            Ok((f(value), end))
+        }
+    }
+
+    // Like map, but `f` can reject the value. The error then covers all the
+    // input this parser consumed.
+    fn try_map<U, F>(self, f: F) -> impl Parser<'a, Output = U>
+    where
+        F: Fn(Self::Output) -> Result<U, String>,
+    {
+        move |input: &'a str, pos: usize| {
+            let (value, end) = self.parse_at(input, pos)?;
+            match f(value) {
+                Ok(mapped) => Ok((mapped, end)),
+                Err(expected) => fail(pos, end, expected),
+            }
+        }
+    }
+
+    fn then<P: Parser<'a>>(self, next: P) -> impl Parser<'a, Output = (Self::Output, P::Output)> {
+        move |input: &'a str, pos: usize| {
+            let (first, pos) = self.parse_at(input, pos)?;
+            let (second, pos) = next.parse_at(input, pos)?;
+            Ok(((first, second), pos))
+        }
+    }
+
+    fn then_ignore<P: Parser<'a>>(self, next: P) -> impl Parser<'a, Output = Self::Output> {
+        self.then(next).map(|(first, _)| first)
+    }
+
+    fn ignore_then<P: Parser<'a>>(self, next: P) -> impl Parser<'a, Output = P::Output> {
+        self.then(next).map(|(_, second)| second)
+    }
+
+    // Tries `self`, and `other` from the same position if that fails. When
+    // both fail the error is the one that got further into the input.
+    fn or<P: Parser<'a, Output = Self::Output>>(
+        self,
+        other: P,
+    ) -> impl Parser<'a, Output = Self::Output> {
+        move |input: &'a str, pos: usize| {
+            let first = match self.parse_at(input, pos) {
+                Ok(result) => return Ok(result),
+                Err(failure) => failure,
+            };
+            let second = match other.parse_at(input, pos) {
+                Ok(result) => return Ok(result),
+                Err(failure) => failure,
+            };
+            if first.start == second.start {
+                fail(
+                    first.start,
+                    first.end.max(second.end),
+                    format!("{} or {}", first.expected, second.expected),
+                )
+            } else if first.start > second.start {
+                Err(first)
+            } else {
+                Err(second)
+            }
+        }
+    }
+
+    fn opt(self) -> impl Parser<'a, Output = Option<Self::Output>> {
+        move |input: &'a str, pos: usize| match self.parse_at(input, pos) {
+            Ok((value, end)) => Ok((Some(value), end)),
+            Err(_) => Ok((None, pos)),
+        }
+    }
+
+    // Discards the parsed value and returns the input it was parsed from.
+    fn recognize(self) -> impl Parser<'a, Output = &'a str> {
+        move |input: &'a str, pos: usize| {
+            let (_, end) = self.parse_at(input, pos)?;
+            Ok((&input[pos..end], end))
+        }
+    }
+}
+
+impl<'a, T, F> Parser<'a> for F
+where
+    F: Fn(&'a str, usize) -> PResult<T>,
+{
+    type Output = T;
+
+    fn parse_at(&self, input: &'a str, pos: usize) -> PResult<T> {
+        self(input, pos)
+    }
+}
+
+// Length in bytes of the character at `pos`, or 0 at the end of the input.
+fn char_len(input: &str, pos: usize) -> usize {
+    input[pos..].chars().next().map_or(0, char::len_utf8)
+}
+
+// Matches `text` exactly.
+pub fn tag<'a>(text: &'static str) -> impl Parser<'a, Output = &'a str> {
+    move |input: &'a str, pos: usize| {
+        if input[pos..].starts_with(text) {
+            Ok((&input[pos..pos + text.len()], pos + text.len()))
+        } else {
+            fail(pos, pos + char_len(input, pos), format!("{:?}", text))
+        }
+    }
+}
+
+// Matches `word` when it isn't just the start of a longer word.
+pub fn keyword<'a>(word: &'static str) -> impl Parser<'a, Output = &'a str> {
+    move |input: &'a str, pos: usize| {
+        let end = pos
+            + input[pos..]
+                .find(|c: char| !c.is_alphanumeric())
+                .unwrap_or(input.len() - pos);
+        if &input[pos..end] == word {
+            Ok((&input[pos..end], end))
+        } else {
+            fail(
+                pos,
+                end.max(pos + char_len(input, pos)),
+                format!("{:?}", word),
+            )
+        }
+    }
+}
+
+// Matches a single character from `chars`.
+pub fn one_char_of<'a>(chars: &'static str) -> impl Parser<'a, Output = char> {
+    move |input: &'a str, pos: usize| match input[pos..].chars().next() {
+        Some(c) if chars.contains(c) => Ok((c, pos + c.len_utf8())),
+        _ => fail(
+            pos,
+            pos + char_len(input, pos),
+            format!("one of {:?}", chars),
+        ),
+    }
+}
+
+// Matches one or more characters accepted by `pred`.
+pub fn take_while1<'a, F>(expected: &'static str, pred: F) -> impl Parser<'a, Output = &'a str>
+where
+    F: Fn(char) -> bool,
+{
+    move |input: &'a str, pos: usize| {
+        let end = pos
+            + input[pos..]
+                .find(|c: char| !pred(c))
+                .unwrap_or(input.len() - pos);
+        if end == pos {
+            return fail(pos, pos + char_len(input, pos), expected);
+        }
+        Ok((&input[pos..end], end))
+    }
+}
+
+// A run of letters.
+pub fn word<'a>() -> impl Parser<'a, Output = &'a str> {
+    take_while1("word", char::is_alphabetic)
+}
+
+fn parse_number<T: FromStr>(digits: &str) -> Result<T, String> {
+    digits
+        .parse::<T>()
+        .map_err(|_| format!("integer in range for {}", std::any::type_name::<T>()))
+}
+
+// A run of decimal digits.
+pub fn unsigned<'a, T: FromStr>() -> impl Parser<'a, Output = T> {
+    take_while1("unsigned integer", |c| c.is_ascii_digit()).try_map(parse_number)
+}
+
+// A run of decimal digits with an optional leading sign.
+pub fn signed<'a, T: FromStr>() -> impl Parser<'a, Output = T> {
+    let digits = take_while1("signed integer", |c| c.is_ascii_digit());
+    one_char_of("+-")
+        .opt()
+        .then(digits)
+        .recognize()
+        .try_map(|text| parse_number(text.trim_start_matches('+')))
+}
+
+// One or more `item`s with a `separator` between each of them. Once a
+// separator matched, an item has to follow.
+pub fn separated_list<'a, P, S>(item: P, separator: S) -> impl Parser<'a, Output = Vec<P::Output>>
+where
+    P: Parser<'a>,
+    S: Parser<'a>,
+{
+    move |input: &'a str, pos: usize| {
+        let (first, mut pos) = item.parse_at(input, pos)?;
+        let mut items = vec![first];
+        while let Ok((_, after_separator)) = separator.parse_at(input, pos) {
+            let (next, end) = item.parse_at(input, after_separator)?;
+            items.push(next);
+            pos = end;
+        }
+        Ok((items, pos))
+    }
+}
+
+// Splits the input at `pos` into lines, tolerating CRLF line endings. Returns
+// the start and the end of the content for each.
+fn line_bounds(input: &str, pos: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let mut start = pos;
+    std::iter::from_fn(move || {
+        if start >= input.len() {
+            return None;
+        }
+        let (end, next) = match input[start..].find('\n') {
+            Some(offset) => (start + offset, start + offset + 1),
+            None => (input.len(), input.len()),
+        };
+        let content_end = if input[start..end].ends_with('\r') {
+            end - 1
+        } else {
+            end
+        };
+        let line = (start, content_end);
+        start = next;
+        Some(line)
+    })
+}
+
+// Runs `line` over the whole of one line, which it has to consume entirely.
+fn parse_full_line<'a, P: Parser<'a>>(
+    line: &P,
+    input: &'a str,
+    start: usize,
+    end: usize,
+) -> Result<P::Output, Failure> {
+    let (value, pos) = line.parse_at(&input[..end], start)?;
+    if pos != end {
+        return Err(Failure {
+            start: pos,
+            end,
+            expected: "end of line".to_owned(),
+        });
+    }
+    Ok(value)
+}
+
+// Parses every remaining line with `line`.
+pub fn lines<'a, P: Parser<'a>>(line: P) -> impl Parser<'a, Output = Vec<P::Output>> {
+    move |input: &'a str, pos: usize| {
+        let mut values = Vec::new();
+        for (start, end) in line_bounds(input, pos) {
+            values.push(parse_full_line(&line, input, start, end)?);
+        }
+        Ok((values, input.len()))
+    }
+}
+
+// Parses every remaining line with `line`, grouping lines into records that
+// are separated by blank lines.
+pub fn records<'a, P: Parser<'a>>(line: P) -> impl Parser<'a, Output = Vec<Vec<P::Output>>> {
+    move |input: &'a str, pos: usize| {
+        let mut records = Vec::new();
+        let mut current = Vec::new();
+        for (start, end) in line_bounds(input, pos) {
+            if input[start..end].trim().is_empty() {
+                if !current.is_empty() {
+                    records.push(std::mem::take(&mut current));
+                }
+                continue;
+            }
+            current.push(parse_full_line(&line, input, start, end)?);
+        }
+        if !current.is_empty() {
+            records.push(current);
+        }
+        Ok((records, input.len()))
+    }
+}
+
+// A parse failure located in the text it happened in, ready to be shown to a
+// person.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub line_no: usize,
+    pub column: usize,
+    pub width: usize,
+    pub line: String,
+    pub expected: String,
+}
+
+impl ParseError {
+    fn new(input: &str, failure: Failure) -> ParseError {
+        let line_start = input[..failure.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = input[failure.start..]
+            .find(['\n', '\r'])
+            .map_or(input.len(), |i| failure.start + i);
+        let span_end = failure.end.min(line_end).max(failure.start);
+        ParseError {
+            line_no: input[..line_start].matches('\n').count() + 1,
+            column: input[line_start..failure.start].chars().count() + 1,
+            width: input[failure.start..span_end].chars().count().max(1),
+            line: input[line_start..line_end].to_owned(),
+            expected: failure.expected,
+        }
+    }
+
+    // For inputs that were parsed one line at a time: places the error on line
+    // `line_no` of the whole file.
+    pub fn at_line(mut self, line_no: usize) -> ParseError {
+        self.line_no += line_no - 1;
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "line {}, column {}: expected {}",
+            self.line_no, self.column, self.expected
+        )?;
+        writeln!(f, "    {}", self.line)?;
+        write!(
+            f,
+            "    {}{}",
+            " ".repeat(self.column - 1),
+            "^".repeat(self.width)
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// Runs `parser` over all of `input`, which it has to consume entirely.
+pub fn parse_all<'a, P: Parser<'a>>(parser: &P, input: &'a str) -> Result<P::Output, ParseError> {
+    match parser.parse_at(input, 0) {
+        Ok((value, end)) if end == input.len() => Ok(value),
+        Ok((_, end)) => Err(ParseError::new(
+            input,
+            Failure {
+                start: end,
+                end: input.len(),
+                expected: "end of input".to_owned(),
+            },
+        )),
+        Err(failure) => Err(ParseError::new(input, failure)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair<'a>() -> impl Parser<'a, Output = (u32, &'a str)> {
+        unsigned::<u32>().then_ignore(tag(" ")).then(word())
+    }
+
+    #[test]
+    fn lines_with_crlf() {
+        let parsed = parse_all(&lines(pair()), "1 a\r\n2 b\r\n3 c").unwrap();
+        assert_eq!(parsed, vec![(1, "a"), (2, "b"), (3, "c")]);
+        let parsed = parse_all(&lines(pair()), "1 a\n2 b\n").unwrap();
+        assert_eq!(parsed, vec![(1, "a"), (2, "b")]);
+    }
+
+    #[test]
+    fn lines_error_location() {
+        let e = parse_all(&lines(pair()), "1 a\n2 b!\n3 c").unwrap_err();
+        assert_eq!((e.line_no, e.column), (2, 4));
+        assert_eq!(e.line, "2 b!");
+        assert_eq!(e.expected, "end of line");
+        let e = parse_all(&lines(pair()), "1 a\r\nx b\r\n").unwrap_err();
+        assert_eq!((e.line_no, e.column), (2, 1));
+        assert_eq!(e.line, "x b");
+    }
+
+    #[test]
+    fn records_split_on_blank_lines() {
+        let input = "1 a\n2 b\n\n  \n3 c\r\n\r\n4 d\n\n";
+        let parsed = parse_all(&records(pair()), input).unwrap();
+        assert_eq!(
+            parsed,
+            vec![vec![(1, "a"), (2, "b")], vec![(3, "c")], vec![(4, "d")]]
+        );
+        assert!(parse_all(&records(pair()), "\n\n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn at_line() {
+        let e = parse_all(&pair(), "1 !").unwrap_err().at_line(7);
+        assert_eq!((e.line_no, e.column), (7, 3));
+        assert_eq!(
+            e.to_string(),
+            "line 7, column 3: expected word\n    1 !\n      ^"
+        );
+    }
+}
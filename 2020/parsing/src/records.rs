@@ -0,0 +1,225 @@
+use crate::{parse_all, ParseError, Parser};
+use std::fmt;
+use std::io::BufRead;
+
+// A group of consecutive non-blank lines, remembering where in the input it
+// started so errors can point at the right line.
+pub struct Record {
+    first_line: usize,
+    text: String,
+}
+
+impl Record {
+    // Line number (starting at 1) of the first line of the record.
+    pub fn first_line(&self) -> usize {
+        self.first_line
+    }
+
+    // The lines of the record joined with '\n', without a trailing newline.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    // The lines of the record along with their line numbers.
+    pub fn lines(&self) -> impl Iterator<Item = (usize, &str)> {
+        (self.first_line..).zip(self.text.split('\n'))
+    }
+
+    // Runs `parser` over the whole record, which it has to consume entirely.
+    pub fn parse<'r, P: Parser<'r>>(&'r self, parser: &P) -> Result<P::Output, ParseError> {
+        parse_all(parser, &self.text).map_err(|e| e.at_line(self.first_line))
+    }
+}
+
+#[derive(Debug)]
+pub enum RecordError {
+    Io {
+        line_no: usize,
+        source: std::io::Error,
+    },
+    Invalid {
+        line_no: usize,
+        message: String,
+    },
+    Parse(ParseError),
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordError::Io { line_no, source } => {
+                write!(f, "line {}: failed to read input: {}", line_no, source)
+            }
+            RecordError::Invalid { line_no, message } => write!(f, "line {}: {}", line_no, message),
+            RecordError::Parse(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+impl From<ParseError> for RecordError {
+    fn from(e: ParseError) -> RecordError {
+        RecordError::Parse(e)
+    }
+}
+
+// Reads blank-line separated records, the layout of passports, customs
+// answers, tickets, card decks and friends. Lines that only hold whitespace
+// count as blank, trailing whitespace and CRLF line endings are dropped, and
+// any number of blank lines may separate records or end the input.
+pub struct RecordReader<R> {
+    lines: std::io::Lines<R>,
+    line_no: usize,
+}
+
+impl<R: BufRead> RecordReader<R> {
+    pub fn new(reader: R) -> RecordReader<R> {
+        RecordReader {
+            lines: reader.lines(),
+            line_no: 0,
+        }
+    }
+
+    // Builds one value per record by feeding it each line in turn, starting
+    // from G::default(). Rejecting a line fails that record with the line's
+    // number.
+    pub fn fold_lines<G, F>(self, mut grow: F) -> impl Iterator<Item = Result<G, RecordError>>
+    where
+        G: Default,
+        F: FnMut(&mut G, &str) -> Result<(), String>,
+    {
+        self.map(move |record| {
+            let record = record?;
+            let mut group = G::default();
+            for (line_no, line) in record.lines() {
+                grow(&mut group, line)
+                    .map_err(|message| RecordError::Invalid { line_no, message })?;
+            }
+            Ok(group)
+        })
+    }
+
+    // Parses each record as a whole with `parse`, typically a wrapper around
+    // `Record::parse`.
+    pub fn parse_each<T, F>(self, mut parse: F) -> impl Iterator<Item = Result<T, RecordError>>
+    where
+        F: FnMut(&Record) -> Result<T, ParseError>,
+    {
+        self.map(move |record| Ok(parse(&record?)?))
+    }
+}
+
+impl<R: BufRead> Iterator for RecordReader<R> {
+    type Item = Result<Record, RecordError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut record: Option<Record> = None;
+        for line in &mut self.lines {
+            self.line_no += 1;
+            let line = match line {
+                Ok(line) => line,
+                Err(source) => {
+                    let line_no = self.line_no;
+                    return Some(Err(RecordError::Io { line_no, source }));
+                }
+            };
+            let line = line.trim_end();
+            if line.is_empty() {
+                if record.is_some() {
+                    break;
+                }
+                continue;
+            }
+            match record.as_mut() {
+                Some(record) => {
+                    record.text.push('\n');
+                    record.text.push_str(line);
+                }
+                None => {
+                    record = Some(Record {
+                        first_line: self.line_no,
+                        text: line.to_owned(),
+                    })
+                }
+            }
+        }
+        record.map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lines, unsigned};
+
+    fn records(input: &str) -> Vec<(usize, String)> {
+        RecordReader::new(input.as_bytes())
+            .map(|r| {
+                let r = r.unwrap();
+                (r.first_line(), r.text().to_owned())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn crlf_and_whitespace_lines() {
+        let input = "a\r\nb  \r\n \t\r\nc\r\n\r\n\r\n  d\n";
+        assert_eq!(
+            records(input),
+            vec![
+                (1, "a\nb".to_owned()),
+                (4, "c".to_owned()),
+                (7, "  d".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn blank_input() {
+        assert!(records("").is_empty());
+        assert!(records("\n  \n\r\n").is_empty());
+    }
+
+    #[test]
+    fn record_lines() {
+        let record = RecordReader::new("\n\nx\ny\n".as_bytes())
+            .next()
+            .unwrap()
+            .unwrap();
+        let lines: Vec<(usize, &str)> = record.lines().collect();
+        assert_eq!(lines, vec![(3, "x"), (4, "y")]);
+    }
+
+    #[test]
+    fn parse_each_points_at_the_file_line() {
+        let input = "1\n2\n\n3\nx\n";
+        let parsed: Vec<Result<Vec<u32>, RecordError>> = RecordReader::new(input.as_bytes())
+            .parse_each(|record| record.parse(&lines(unsigned::<u32>())))
+            .collect();
+        assert_eq!(parsed[0].as_ref().unwrap(), &vec![1, 2]);
+        match &parsed[1] {
+            Err(RecordError::Parse(e)) => assert_eq!((e.line_no, e.column), (5, 1)),
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn fold_lines_rejects_with_line_number() {
+        let input = "ab\r\nc\n\nd\n1\n";
+        let folded: Vec<Result<String, RecordError>> = RecordReader::new(input.as_bytes())
+            .fold_lines(|text: &mut String, line| {
+                if !line.chars().all(|c| c.is_ascii_lowercase()) {
+                    return Err(format!("not a letter: {}", line));
+                }
+                text.push_str(line);
+                Ok(())
+            })
+            .collect();
+        assert_eq!(folded[0].as_ref().unwrap(), "abc");
+        assert_eq!(
+            folded[1].as_ref().unwrap_err().to_string(),
+            "line 5: not a letter: 1"
+        );
+    }
+}